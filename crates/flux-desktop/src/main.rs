@@ -1,57 +1,82 @@
-use flux::settings::{BlendMethod, ColorScheme, Noise, Settings};
+use flux::settings::{
+    AdvectionScheme, BlendMethod, Boundary, ColorInterpolation, ColorScheme, Noise, NoiseKind,
+    PressureSolver, Settings,
+};
 use flux::Flux;
-use glutin::event::{Event, WindowEvent};
+use glutin::dpi::LogicalPosition;
+use glutin::event::{ElementState, Event, MouseButton, Touch, TouchPhase, WindowEvent};
 use glutin::event_loop::{ControlFlow, EventLoop};
 use glutin::window::Window;
 use glutin::PossiblyCurrent;
 use std::rc::Rc;
 
-const settings: Settings = Settings {
-    viscosity: 1.0,
-    velocity_dissipation: 0.0,
-    starting_pressure: 0.8,
-    fluid_size: 128,
-    fluid_simulation_frame_rate: 30.0,
-    diffusion_iterations: 20,
-    pressure_iterations: 60,
-    color_scheme: ColorScheme::Plasma,
-    line_length: 180.0,
-    line_width: 6.0,
-    line_begin_offset: 0.5,
-    line_fade_out_length: 0.005,
-    spring_stiffness: 0.2,
-    spring_variance: 0.25,
-    spring_mass: 2.0,
-    spring_damping: 2.0,
-    spring_rest_length: 0.0,
-    advection_direction: 1.0,
-    adjust_advection: 22.0,
-    max_line_velocity: 0.02,
-    grid_spacing: 20,
-    view_scale: 1.2,
-    noise_channel_1: Noise {
-        scale: 0.9,
-        multiplier: 0.20,
-        offset_1: 2.0,
-        offset_2: 8.0,
-        offset_increment: 0.01,
-        delay: 0.5,
-        blend_duration: 3.5,
-        blend_threshold: 0.4,
-        blend_method: BlendMethod::Curl,
-    },
-    noise_channel_2: Noise {
-        scale: 25.0,
-        multiplier: 0.08,
-        offset_1: 3.0,
-        offset_2: 2.0,
-        offset_increment: 0.02,
-        delay: 0.15,
-        blend_duration: 1.0,
-        blend_threshold: 0.0,
-        blend_method: BlendMethod::Curl,
-    },
-};
+fn settings() -> Settings {
+    Settings {
+        viscosity: 1.0,
+        velocity_dissipation: 0.0,
+        starting_pressure: 0.8,
+        fluid_size: 128,
+        fluid_simulation_frame_rate: 30.0,
+        diffusion_iterations: 20,
+        pressure_iterations: 60,
+        advection_scheme: AdvectionScheme::SemiLagrangian,
+        pressure_solver: PressureSolver::Jacobi,
+        warm_start_pressure: false,
+        pressure_tolerance: 0.0,
+        boundary: Boundary::FreeSlip,
+        vorticity_strength: 0.0,
+        color_scheme: ColorScheme::Plasma,
+        color_interpolation: ColorInterpolation::Srgb,
+        line_length: 180.0,
+        line_width: 6.0,
+        line_begin_offset: 0.5,
+        line_fade_out_length: 0.005,
+        spring_stiffness: 0.2,
+        spring_variance: 0.25,
+        spring_mass: 2.0,
+        spring_damping: 2.0,
+        spring_rest_length: 0.0,
+        advection_direction: 1.0,
+        adjust_advection: 22.0,
+        max_line_velocity: 0.02,
+        grid_spacing: 20,
+        view_scale: 1.2,
+        pointer_radius: 80.0,
+        pointer_strength: 0.4,
+        obstacles: Vec::new(),
+        dye: None,
+        temperature: None,
+        noise_channels: vec![
+            Noise {
+                scale: 0.9,
+                multiplier: 0.20,
+                offset_1: 2.0,
+                offset_2: 8.0,
+                offset_increment: 0.01,
+                delay: 0.5,
+                blend_duration: 3.5,
+                blend_threshold: 0.4,
+                blend_method: BlendMethod::Curl,
+                kind: NoiseKind::Simplex,
+            },
+            Noise {
+                scale: 25.0,
+                multiplier: 0.08,
+                offset_1: 3.0,
+                offset_2: 2.0,
+                offset_increment: 0.02,
+                delay: 0.15,
+                blend_duration: 1.0,
+                blend_threshold: 0.0,
+                blend_method: BlendMethod::Curl,
+                kind: NoiseKind::Simplex,
+            },
+        ],
+    }
+}
+
+// Touch points use their own ids, offset by one to stay clear of the mouse.
+const MOUSE_POINTER_ID: u64 = 0;
 
 fn main() {
     let logical_size = glutin::dpi::LogicalSize::new(1200, 900);
@@ -66,11 +91,14 @@ fn main() {
         logical_size.height,
         physical_size.width,
         physical_size.height,
-        &Rc::new(settings),
+        &Rc::new(settings()),
     )
     .unwrap();
 
     let start = std::time::Instant::now();
+    let mut cursor_position: Option<LogicalPosition<f32>> = None;
+    // Only push the fluid while the left button is held, not on every hover
+    let mut is_dragging = false;
 
     event_loop.run(move |event, _, control_flow| {
         let next_frame_time =
@@ -99,6 +127,60 @@ fn main() {
                         physical_size.height,
                     );
                 }
+                WindowEvent::CursorMoved { position, .. } => {
+                    let position = position.to_logical(window.window().scale_factor());
+                    if is_dragging {
+                        flux.move_pointer(MOUSE_POINTER_ID, position.x, position.y);
+                    }
+                    cursor_position = Some(position);
+                }
+                WindowEvent::CursorLeft { .. } => {
+                    flux.release_pointer(MOUSE_POINTER_ID);
+                    cursor_position = None;
+                }
+                WindowEvent::MouseInput {
+                    state,
+                    button: MouseButton::Left,
+                    ..
+                } => match state {
+                    ElementState::Pressed => {
+                        is_dragging = true;
+                        if let Some(position) = cursor_position {
+                            flux.move_pointer(MOUSE_POINTER_ID, position.x, position.y);
+                        }
+                    }
+                    ElementState::Released => {
+                        is_dragging = false;
+                        flux.release_pointer(MOUSE_POINTER_ID);
+                    }
+                },
+                WindowEvent::MouseInput {
+                    state: ElementState::Pressed,
+                    button: MouseButton::Right,
+                    ..
+                } => {
+                    if let Some(position) = cursor_position {
+                        flux.twirl_at(position.x, position.y);
+                    }
+                }
+                WindowEvent::Touch(Touch {
+                    phase,
+                    location,
+                    id,
+                    ..
+                }) => {
+                    let pointer_id = id + 1;
+                    match phase {
+                        TouchPhase::Started | TouchPhase::Moved => {
+                            let location: LogicalPosition<f32> =
+                                location.to_logical(window.window().scale_factor());
+                            flux.move_pointer(pointer_id, location.x, location.y);
+                        }
+                        TouchPhase::Ended | TouchPhase::Cancelled => {
+                            flux.release_pointer(pointer_id)
+                        }
+                    }
+                }
                 WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
                 _ => (),
             },
@@ -0,0 +1,150 @@
+use flux::headless::HeadlessFlux;
+use flux::settings::Settings;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+const USAGE: &str = "\
+Render Flux to a sequence of PNG frames.
+
+USAGE:
+    flux-render <SETTINGS> [OPTIONS]
+
+ARGS:
+    <SETTINGS>    Path to a settings JSON file
+
+OPTIONS:
+    --width <PIXELS>       Width of each frame [default: 1920]
+    --height <PIXELS>      Height of each frame [default: 1080]
+    --fps <RATE>           Frames per second [default: 60]
+    --duration <SECONDS>   Length of the sequence [default: 10]
+    --output <DIR>         Directory to write the frames to [default: frames]
+";
+
+struct Options {
+    settings_path: PathBuf,
+    width: u32,
+    height: u32,
+    fps: f32,
+    duration: f32,
+    output: PathBuf,
+}
+
+impl Options {
+    // Returns `None` if the usage was asked for.
+    fn from_args(mut args: impl Iterator<Item = String>) -> Result<Option<Self>, String> {
+        let mut settings_path = None;
+        let mut options = Options {
+            settings_path: PathBuf::new(),
+            width: 1920,
+            height: 1080,
+            fps: 60.0,
+            duration: 10.0,
+            output: PathBuf::from("frames"),
+        };
+
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| format!("Missing value for `{}`", arg))
+            };
+
+            match arg.as_str() {
+                "--width" => options.width = parse(&value()?)?,
+                "--height" => options.height = parse(&value()?)?,
+                "--fps" => options.fps = parse(&value()?)?,
+                "--duration" => options.duration = parse(&value()?)?,
+                "--output" => options.output = PathBuf::from(value()?),
+                "-h" | "--help" => return Ok(None),
+                _ if settings_path.is_none() && !arg.starts_with("--") => {
+                    settings_path = Some(PathBuf::from(arg))
+                }
+                _ => return Err(format!("Unexpected argument `{}`\n\n{}", arg, USAGE)),
+            }
+        }
+
+        options.settings_path = settings_path.ok_or_else(|| USAGE.to_string())?;
+
+        if options.width == 0 || options.height == 0 {
+            return Err("The frame size must be at least 1x1".to_string());
+        }
+        if !(options.fps.is_finite() && options.fps > 0.0) {
+            return Err("The frame rate must be a positive number".to_string());
+        }
+        if !(options.duration.is_finite() && options.duration > 0.0) {
+            return Err("The duration must be a positive number".to_string());
+        }
+
+        Ok(Some(options))
+    }
+
+    fn frame_count(&self) -> u32 {
+        (self.duration * self.fps).round() as u32
+    }
+}
+
+fn parse<T: std::str::FromStr>(value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("Cannot parse `{}`", value))
+}
+
+fn main() {
+    let options = match Options::from_args(std::env::args().skip(1)) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            print!("{}", USAGE);
+            return;
+        }
+        Err(msg) => {
+            eprintln!("{}", msg);
+            std::process::exit(2);
+        }
+    };
+
+    if let Err(err) = render(&options) {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
+}
+
+fn render(options: &Options) -> Result<(), Box<dyn Error>> {
+    let settings_file = File::open(&options.settings_path)?;
+    let settings: Settings = serde_json::from_reader(settings_file)?;
+
+    let mut flux = HeadlessFlux::new(options.width, options.height, &Rc::new(settings))?;
+    // Step the simulation by exactly one frame each time, regardless of how
+    // long it takes to render.
+    flux.frame_time = 1.0 / options.fps;
+
+    fs::create_dir_all(&options.output)?;
+
+    let frame_count = options.frame_count();
+    let digits = frame_count.to_string().len().max(5);
+
+    for frame in 0..frame_count {
+        let pixels = flux.render_frame();
+        let path = options
+            .output
+            .join(format!("frame-{:0width$}.png", frame + 1, width = digits));
+        write_png(&path, options.width, options.height, &pixels)?;
+
+        println!("Rendered frame {} of {}", frame + 1, frame_count);
+    }
+
+    Ok(())
+}
+
+fn write_png(path: &Path, width: u32, height: u32, pixels: &[u8]) -> Result<(), Box<dyn Error>> {
+    let file = File::create(path)?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(pixels)?;
+
+    Ok(())
+}
@@ -24,9 +24,13 @@ pub struct Flux {
 #[wasm_bindgen]
 impl Flux {
     #[wasm_bindgen(setter)]
-    pub fn set_settings(&mut self, settings_object: &JsValue) {
-        let settings: flux::settings::Settings = settings_object.into_serde().unwrap();
-        self.id.update(&Rc::new(settings));
+    pub fn set_settings(&mut self, settings_object: &JsValue) -> Result<(), JsValue> {
+        let settings: flux::settings::Settings = settings_object
+            .into_serde()
+            .map_err(|msg| JsValue::from_str(&msg.to_string()))?;
+        self.id
+            .update(&Rc::new(settings))
+            .map_err(|err| JsValue::from_str(&err.to_string()))
     }
 
     #[wasm_bindgen(constructor)]
@@ -66,10 +70,70 @@ impl Flux {
         })
     }
 
+    // What the browser’s WebGL context supports, to attach to bug reports.
+    pub fn capabilities(&self) -> JsValue {
+        JsValue::from_serde(self.id.capabilities()).unwrap()
+    }
+
     pub fn animate(&mut self, timestamp: f32) {
         self.id.animate(timestamp);
     }
 
+    // Pointers are tracked by their `pointerId`, so each finger on a
+    // touchscreen pushes the fluid on its own. Coordinates are in CSS pixels,
+    // relative to the canvas.
+    pub fn move_pointer(&mut self, id: u32, x: f32, y: f32) {
+        self.id.move_pointer(id.into(), x, y);
+    }
+
+    pub fn release_pointer(&mut self, id: u32) {
+        self.id.release_pointer(id.into());
+    }
+
+    pub fn twirl_at(&mut self, x: f32, y: f32) {
+        self.id.twirl_at(x, y);
+    }
+
+    // Flow around an image, like a logo. `data` has one byte per pixel, from
+    // the top row down, and anything above 127 is solid. Pass an empty array
+    // to remove it.
+    pub fn set_obstacle_mask(
+        &mut self,
+        width: u32,
+        height: u32,
+        data: Vec<u8>,
+    ) -> Result<(), JsValue> {
+        let mask = if data.is_empty() {
+            None
+        } else {
+            let mask = flux::ObstacleMask::new(width, height, data)
+                .ok_or_else(|| JsValue::from_str("The mask doesn’t match its size"))?;
+            Some(mask)
+        };
+
+        self.id
+            .set_obstacle_mask(mask)
+            .map_err(|err| JsValue::from_str(&err.to_string()))
+    }
+
+    // Save the state of the animation as bytes, to resume it later with
+    // `restore`, like after a reload.
+    pub fn snapshot(&self) -> Result<Vec<u8>, JsValue> {
+        self.id
+            .snapshot()
+            .map(|state| state.to_bytes())
+            .map_err(|err| JsValue::from_str(&err.to_string()))
+    }
+
+    pub fn restore(&mut self, data: Vec<u8>) -> Result<(), JsValue> {
+        let state = flux::FluxState::from_bytes(&data)
+            .map_err(|err| JsValue::from_str(&err.to_string()))?;
+
+        self.id
+            .restore(&state)
+            .map_err(|err| JsValue::from_str(&err.to_string()))
+    }
+
     pub fn resize(&mut self, logical_width: u32, logical_height: u32) {
         if (self.logical_width != logical_width) || (self.logical_height != logical_height) {
             let (physical_width, physical_height) =
@@ -137,9 +201,12 @@ pub fn get_rendering_context(
 
     let gl = if let Ok(Some(gl)) = canvas.get_context_with_context_options("webgl2", &options) {
         let gl = gl.dyn_into::<GL>()?;
+        // None of these are required. Flux picks a texture format for the
+        // simulation based on what’s available.
         gl.get_extension("OES_texture_float")?;
         gl.get_extension("OES_texture_float_linear")?;
         gl.get_extension("EXT_color_buffer_float")?;
+        gl.get_extension("EXT_color_buffer_half_float")?;
         gl.get_extension("EXT_float_blend")?;
 
         gl.disable(GL::BLEND);
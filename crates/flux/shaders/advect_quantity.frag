@@ -0,0 +1,28 @@
+precision highp float;
+precision highp sampler2D;
+
+#include "common/fluid_uniforms.glsl"
+
+uniform sampler2D velocityTexture;
+uniform sampler2D quantityTexture;
+uniform sampler2D obstacleTexture;
+uniform float uDissipation;
+
+in vec2 textureCoord;
+out vec4 newQuantity;
+
+#include "common/obstacles.glsl"
+
+// Carry a quantity, like dye or temperature, along with the fluid. This works
+// like `advection.frag`, but with its own dissipation.
+void main() {
+  vec2 velocity = readField(velocityTexture, textureCoord).xy;
+  vec2 pastCoord = textureCoord - (epsilon * deltaT * velocity);
+  float decay = 1.0 + uDissipation * deltaT;
+  newQuantity = texture(quantityTexture, pastCoord) / decay;
+
+  // Nothing seeps into obstacles.
+  if (isSolid(textureCoord)) {
+    newQuantity = vec4(0.0);
+  }
+}
@@ -1,22 +1,22 @@
 precision highp float;
 precision highp sampler2D;
 
-layout(std140) uniform FluidUniforms
-{
-  highp float deltaT;
-  highp float epsilon;
-  highp float halfEpsilon;
-  highp float dissipation;
-  highp vec2 uTexelSize;
-  lowp float pad1;
-  lowp float pad2;
-};
+#include "common/fluid_uniforms.glsl"
 
 uniform sampler2D inputTexture;
 uniform sampler2D velocityTexture;
+uniform sampler2D obstacleTexture;
+// 1 traces back in time. -1 traces forward, which MacCormack and BFECC use to
+// estimate the error of the step.
+uniform float uDirection;
+// Clamp the result to the velocities it was interpolated from
+uniform bool uLimit;
 
 in vec2 textureCoord;
-out vec2 newVelocity;
+FIELD_OUTPUT(newVelocity);
+
+#include "common/obstacles.glsl"
+#include "common/clamp_to_neighbours.glsl"
 
 void main() {
   vec2 offset = vec2(0.0, 0.0);
@@ -37,10 +37,19 @@ void main() {
     scale.y = -1.0;
   }
 
-  vec2 velocity = scale * texture(velocityTexture, textureCoord + offset).xy;
+  vec2 velocity = scale * readField(velocityTexture, textureCoord + offset).xy;
+
+  vec2 pastCoord = textureCoord - (uDirection * epsilon * deltaT * velocity);
+  vec2 pastVelocity = readField(inputTexture, pastCoord).rg;
+  if (uLimit) {
+    pastVelocity = clampToNeighbours(pastVelocity, velocityTexture, pastCoord);
+  }
 
-  vec2 pastCoord = textureCoord - (epsilon * deltaT * velocity);
-  vec2 pastVelocity = texture(inputTexture, pastCoord).rg;
-  float decay = 1.0 + dissipation * deltaT;
+  // Tracing forward undoes the decay too.
+  float decay = pow(1.0 + dissipation * deltaT, uDirection);
   newVelocity = pastVelocity / decay;
+
+  if (isSolid(textureCoord) || (boundary == NO_SLIP && isEdge(textureCoord))) {
+    newVelocity = vec2(0.0);
+  }
 }
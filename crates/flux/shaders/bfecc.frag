@@ -0,0 +1,17 @@
+precision highp float;
+precision highp sampler2D;
+
+uniform sampler2D velocityTexture;
+// A semi-Lagrangian step forward, traced back again
+uniform sampler2D backwardTexture;
+
+in vec2 textureCoord;
+FIELD_OUTPUT(newVelocity);
+
+// Push the velocity against half the error of a round trip, ready for the
+// final semi-Lagrangian step.
+void main() {
+  vec2 velocity = readField(velocityTexture, textureCoord).xy;
+  vec2 backward = readField(backwardTexture, textureCoord).xy;
+  newVelocity = velocity + 0.5 * (velocity - backward);
+}
@@ -0,0 +1,24 @@
+precision highp float;
+precision highp sampler2D;
+
+#include "common/noise_uniforms.glsl"
+
+uniform float uBlendProgress;
+uniform float uAmount;
+// What to add, per unit of `uAmount`, like in `splat_quantity.frag`
+uniform vec4 uValue;
+
+uniform sampler2D inputTexture;
+uniform sampler2D noiseTexture;
+
+in vec2 textureCoord;
+out vec4 outputValue;
+
+// Add a quantity, like dye or heat, wherever the noise pushes the fluid
+// hardest
+void main() {
+  float strength = length(readField(noiseTexture, textureCoord).xy);
+
+  vec4 inputValue = texture(inputTexture, textureCoord);
+  outputValue = inputValue + uBlendProgress * uMultiplier * uAmount * strength * uValue;
+}
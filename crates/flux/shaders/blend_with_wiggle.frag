@@ -1,15 +1,7 @@
 precision highp float;
 precision highp sampler2D;
 
-layout(std140) uniform NoiseUniforms {
-  highp float uFrequency;
-  highp float uOffset1;
-  highp float uOffset2;
-  highp float uMultiplier;
-  highp vec2 uTexelSize;
-  highp float uBlendThreshold;
-  lowp float pad2;
-};
+#include "common/noise_uniforms.glsl"
 
 uniform float uBlendProgress;
 
@@ -17,7 +9,7 @@ uniform sampler2D inputTexture;
 uniform sampler2D noiseTexture;
 
 in vec2 textureCoord;
-out vec2 outputValue;
+FIELD_OUTPUT(outputValue);
 
 vec2 clockwisePerpendicular(in vec2 vector) {
   return vec2(vector.y, -vector.x);
@@ -28,8 +20,8 @@ vec2 anticlockwisePerpendicular(in vec2 vector) {
 }
 
 void main() {
-  float noise = texture(noiseTexture, textureCoord).x;
-  vec2 inputValue = texture(inputTexture, textureCoord).xy;
+  float noise = readField(noiseTexture, textureCoord).x;
+  vec2 inputValue = readField(inputTexture, textureCoord).xy;
 
   vec2 direction;
   if (length(inputValue) == 0.0) {
@@ -0,0 +1,19 @@
+precision highp float;
+precision highp sampler2D;
+
+#include "common/fluid_uniforms.glsl"
+
+uniform sampler2D velocityTexture;
+uniform sampler2D temperatureTexture;
+uniform float uBuoyancy;
+
+in vec2 textureCoord;
+FIELD_OUTPUT(newVelocity);
+
+// Hot fluid rises and cold fluid sinks, relative to an ambient temperature of
+// zero.
+void main() {
+  vec2 velocity = readField(velocityTexture, textureCoord).xy;
+  float temperature = texture(temperatureTexture, textureCoord).x;
+  newVelocity = velocity + vec2(0.0, deltaT * uBuoyancy * temperature);
+}
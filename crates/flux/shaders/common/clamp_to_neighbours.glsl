@@ -0,0 +1,10 @@
+// Keep a value within the range of the four texels around `coord`, so that
+// the error correction can’t overshoot.
+vec2 clampToNeighbours(vec2 value, sampler2D field, vec2 coord) {
+  vec2 corner = (floor(coord / uTexelSize - 0.5) + 0.5) * uTexelSize;
+  vec2 a = readField(field, corner).xy;
+  vec2 b = readField(field, corner + vec2(uTexelSize.x, 0.0)).xy;
+  vec2 c = readField(field, corner + vec2(0.0, uTexelSize.y)).xy;
+  vec2 d = readField(field, corner + uTexelSize).xy;
+  return clamp(value, min(min(a, b), min(c, d)), max(max(a, b), max(c, d)));
+}
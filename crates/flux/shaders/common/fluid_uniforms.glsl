@@ -0,0 +1,11 @@
+// Must match `Uniforms` in fluid.rs
+layout(std140) uniform FluidUniforms
+{
+  highp float deltaT;
+  highp float epsilon;
+  highp float halfEpsilon;
+  highp float dissipation;
+  highp vec2 uTexelSize;
+  int boundary;
+  lowp float pad2;
+};
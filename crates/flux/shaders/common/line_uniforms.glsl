@@ -0,0 +1,8 @@
+// Must match `LineUniforms` in drawer.rs
+layout(std140) uniform LineUniforms
+{
+  mediump float uLineWidth;
+  mediump float uLineLength;
+  mediump float uLineBeginOffset;
+  mediump float uLineFadeOutLength;
+};
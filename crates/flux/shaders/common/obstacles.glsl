@@ -0,0 +1,11 @@
+// Needs an `obstacleTexture` uniform.
+
+// Obstacles are 1 in the mask, and the fluid is 0.
+bool isSolid(vec2 coord) {
+  return texture(obstacleTexture, coord).x > 0.5;
+}
+
+// The fluid sticks to the walls in the outermost cells.
+bool isEdge(vec2 coord) {
+  return any(lessThan(coord, uTexelSize)) || any(greaterThan(coord, 1.0 - uTexelSize));
+}
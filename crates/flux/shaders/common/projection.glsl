@@ -0,0 +1,6 @@
+// Must match `Projection` in drawer.rs
+layout(std140) uniform Projection
+{
+  mat4 uProjection;
+  mat4 uView;
+};
@@ -0,0 +1,23 @@
+precision mediump float;
+precision highp sampler2D;
+
+#include "common/fluid_uniforms.glsl"
+
+uniform sampler2D velocityTexture;
+
+in highp vec2 textureCoord;
+in vec2 vL;
+in vec2 vR;
+in vec2 vT;
+in vec2 vB;
+FIELD_OUTPUT(newCurl);
+
+void main() {
+  float L = readField(velocityTexture, vL).y;
+  float R = readField(velocityTexture, vR).y;
+  float T = readField(velocityTexture, vT).x;
+  float B = readField(velocityTexture, vB).x;
+
+  float curl = halfEpsilon * (R - L - T + B);
+  newCurl = vec2(curl, 0.0);
+}
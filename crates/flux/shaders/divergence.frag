@@ -1,38 +1,42 @@
 precision mediump float;
 precision highp sampler2D;
 
-layout(std140) uniform FluidUniforms
-{
-  highp float deltaT;
-  highp float epsilon;
-  highp float halfEpsilon;
-  highp float dissipation;
-  highp vec2 uTexelSize;
-  lowp float pad1;
-  lowp float pad2;
-};
+#include "common/fluid_uniforms.glsl"
 
 uniform sampler2D velocityTexture;
+uniform sampler2D obstacleTexture;
 
 in highp vec2 textureCoord;
 in vec2 vL;
 in vec2 vR;
 in vec2 vT;
 in vec2 vB;
-out vec2 newDivergence;
+FIELD_OUTPUT(newDivergence);
+
+#include "common/obstacles.glsl"
 
 void main() {
-  float L = texture(velocityTexture, vL).x;
-  float R = texture(velocityTexture, vR).x;
-  float T = texture(velocityTexture, vT).y;
-  float B = texture(velocityTexture, vB).y;
+  float L = readField(velocityTexture, vL).x;
+  float R = readField(velocityTexture, vR).x;
+  float T = readField(velocityTexture, vT).y;
+  float B = readField(velocityTexture, vB).y;
+
+  // Reflect the velocity at solid walls. Periodic edges wrap around to the
+  // other side instead, and open edges let the fluid through.
+  vec2 velocity = readField(velocityTexture, textureCoord).xy;
+  if (boundary == FREE_SLIP || boundary == NO_SLIP) {
+    if (vL.x < 0.0) { L = -velocity.x; }
+    if (vR.x > 1.0) { R = -velocity.x; }
+    if (vT.y > 1.0) { T = -velocity.y; }
+    if (vB.y < 0.0) { B = -velocity.y; }
+  }
 
-  vec2 velocity = texture(velocityTexture, textureCoord).xy;
-  if (vL.x < 0.0) { L = -velocity.x; }
-  if (vR.x > 1.0) { R = -velocity.x; }
-  if (vT.y > 1.0) { T = -velocity.y; }
-  if (vB.y < 0.0) { B = -velocity.y; }
+  // Reflect off obstacles, just like the walls.
+  if (isSolid(vL)) { L = -velocity.x; }
+  if (isSolid(vR)) { R = -velocity.x; }
+  if (isSolid(vT)) { T = -velocity.y; }
+  if (isSolid(vB)) { B = -velocity.y; }
 
   float div = halfEpsilon * (R - L + T - B);
-  newDivergence = vec2(div, 0.0);
+  newDivergence = vec2(isSolid(textureCoord) ? 0.0 : div, 0.0);
 }
@@ -0,0 +1,30 @@
+precision mediump float;
+
+#include "common/noise_uniforms.glsl"
+
+uniform vec2 uResolution;
+uniform float uWarpFrequency;
+uniform float uWarpStrength;
+
+FIELD_OUTPUT(noise);
+
+#include "common/simplex.glsl"
+
+void main() {
+  vec2 st = gl_FragCoord.xy / uResolution.xy;
+  st.x *= uResolution.x / uResolution.y;
+
+  // Offset the sampling point by a second, lower frequency noise field. The
+  // constant offsets decorrelate the two warp components.
+  vec2 warpPoint = st * uWarpFrequency;
+  vec2 warp = vec2(
+    snoise(vec3(warpPoint, uOffset1 + 17.3)),
+    snoise(vec3(warpPoint + vec2(5.2, 1.3), uOffset2 + 31.7))
+  );
+  vec2 p = (st + uWarpStrength * warp) * uFrequency;
+
+  float sx = snoise(vec3(p, uOffset1));
+  float sy = snoise(vec3(p, uOffset2));
+
+  noise = vec2(sx, sy);
+}
@@ -0,0 +1,12 @@
+precision mediump float;
+precision highp sampler2D;
+
+in vec2 textureCoord;
+uniform sampler2D dyeTexture;
+uniform float uOpacity;
+out vec4 fragColor;
+
+void main() {
+  vec3 dye = texture(dyeTexture, textureCoord).rgb;
+  fragColor = vec4(uOpacity * min(dye, vec3(1.0)), 1.0);
+}
@@ -0,0 +1,11 @@
+in vec3 position;
+out vec2 textureCoord;
+
+#include "common/projection.glsl"
+
+// Cover the fluid with the dye, scaled by the view like the lines, so that the
+// dye stays under the lines it flows with.
+void main() {
+  gl_Position = uView * vec4(position, 1.0);
+  textureCoord = position.xy * 0.5 + 0.5;
+}
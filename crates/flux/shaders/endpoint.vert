@@ -1,21 +1,13 @@
 #define PI 3.1415926535897932384626433832795
 precision highp float;
 
-layout(std140) uniform Projection
-{
-  mat4 uProjection;
-  mat4 uView;
-};
+#include "common/projection.glsl"
 
-layout(std140) uniform LineUniforms
-{
-  mediump float uLineWidth;
-  mediump float uLineLength;
-  mediump float uLineBeginOffset;
-  mediump float uLineFadeOutLength;
-};
+#include "common/line_uniforms.glsl"
 
 uniform float uOrientation;
+// One of `ADD`, `SCREEN` or `LIGHTEN`. See `DyeBlendMode`.
+uniform int uBlendMode;
 
 in vec2 vertex;
 in vec2 basepoint;
@@ -61,8 +53,11 @@ void main() {
 
   gl_Position = uProjection * uView * translate(endpoint) * rotationMatrix * modelMatrix * vec4(vertex, 0.0, 1.0);
 
+  // The colors are premultiplied, to match `line.frag`.
+  vec3 endpointColor = iColor.rgb * iEndpointOpacity;
+
   if (uOrientation > 0.0) {
-    vColor = vec4(iColor.rgb, iEndpointOpacity);
+    vColor = vec4(endpointColor, iEndpointOpacity);
   } else {
     // The color of the lower half of the endpoint is less obvious. We’re
     // drawing over part of the line, so to match the color of the upper
@@ -70,12 +65,19 @@ void main() {
     // color of the line underneath, so we can reverse the blend equation to get
     // the right color.
     //
-    // GL_BLEND(SRC_ALPHA, ONE) = srcColor * srcAlpha + dstColor * srcAlpha
-    // = vColor * vEndpointOpacity + vColor * vLineOpacity
+    // ADD:     endpointColor = vColor + lineColor
+    // SCREEN:  endpointColor = vColor + lineColor * (1 - vColor)
+    // LIGHTEN: endpointColor = max(vColor, lineColor)
     //
-    // Remember, we’ve already premultiplied our colors! The opacity should be
-    // 1.0 to disable more opacity blending!
-    vec3 premultipliedLineColor = iColor.rgb * iLineOpacity;
-    vColor = vec4(iColor.rgb * iEndpointOpacity - premultipliedLineColor, 1.0);
+    // The opacity is 1.0, because it’s already part of the color.
+    vec3 lineColor = iColor.rgb * iLineOpacity;
+
+    if (uBlendMode == SCREEN) {
+      vColor = vec4((endpointColor - lineColor) / max(1.0 - lineColor, 0.001), 1.0);
+    } else if (uBlendMode == LIGHTEN) {
+      vColor = vec4(endpointColor, 1.0);
+    } else {
+      vColor = vec4(endpointColor - lineColor, 1.0);
+    }
   }
 }
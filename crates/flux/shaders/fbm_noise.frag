@@ -0,0 +1,43 @@
+precision mediump float;
+
+#include "common/noise_uniforms.glsl"
+
+uniform vec2 uResolution;
+uniform int uOctaves;
+uniform float uLacunarity;
+uniform float uGain;
+
+FIELD_OUTPUT(noise);
+
+#include "common/simplex.glsl"
+
+// Sum octaves of simplex noise, normalized back to the range of a single
+// octave.
+float fbm(vec3 v) {
+  float value = 0.0;
+  float amplitude = 1.0;
+  float totalAmplitude = 0.0;
+
+  for (int i = 0; i < MAX_OCTAVES; i++) {
+    if (i >= uOctaves) {
+      break;
+    }
+
+    value += amplitude * snoise(v);
+    totalAmplitude += amplitude;
+    v.xy *= uLacunarity;
+    amplitude *= uGain;
+  }
+
+  return value / max(totalAmplitude, 0.0001);
+}
+
+void main() {
+  vec2 st = gl_FragCoord.xy / uResolution.xy;
+  st.x *= uResolution.x / uResolution.y;
+
+  float sx = fbm(vec3(st * uFrequency, uOffset1));
+  float sy = fbm(vec3(st * uFrequency, uOffset2));
+
+  noise = vec2(sx, sy);
+}
@@ -11,19 +11,9 @@ in mediump float iLineWidth;
 in mediump float iLineOpacity;
 in mediump float iEndpointOpacity;
 
-layout(std140) uniform Projection
-{
-  mat4 uProjection;
-  mat4 uView;
-};
-
-layout(std140) uniform LineUniforms
-{
-  mediump float uLineWidth;
-  mediump float uLineLength;
-  mediump float uLineBeginOffset;
-  mediump float uLineFadeOutLength;
-};
+#include "common/projection.glsl"
+
+#include "common/line_uniforms.glsl"
 
 out vec2 vVertex;
 out vec3 vColor;
@@ -0,0 +1,34 @@
+precision highp float;
+precision highp sampler2D;
+
+#include "common/fluid_uniforms.glsl"
+
+uniform sampler2D velocityTexture;
+// The velocity after a semi-Lagrangian step
+uniform sampler2D forwardTexture;
+// The forward step, traced back again
+uniform sampler2D backwardTexture;
+uniform sampler2D obstacleTexture;
+
+in vec2 textureCoord;
+FIELD_OUTPUT(newVelocity);
+
+#include "common/obstacles.glsl"
+#include "common/clamp_to_neighbours.glsl"
+
+// Correct the forward step by half the error found when tracing it back to
+// where it started.
+void main() {
+  vec2 velocity = readField(velocityTexture, textureCoord).xy;
+  vec2 forward = readField(forwardTexture, textureCoord).xy;
+  vec2 backward = readField(backwardTexture, textureCoord).xy;
+
+  float decay = 1.0 + dissipation * deltaT;
+  vec2 pastCoord = textureCoord - (epsilon * deltaT * velocity);
+  vec2 corrected = forward * decay + 0.5 * (velocity - backward);
+  newVelocity = clampToNeighbours(corrected, velocityTexture, pastCoord) / decay;
+
+  if (isSolid(textureCoord) || (boundary == NO_SLIP && isEdge(textureCoord))) {
+    newVelocity = vec2(0.0);
+  }
+}
@@ -1,15 +1,6 @@
 precision highp float;
 
-layout(std140) uniform NoiseUniforms
-{
-  highp float uFrequency;
-  highp float uOffset1;
-  highp float uOffset2;
-  highp float uMultiplier;
-  highp vec2 uTexelSize;
-  highp float uBlendThreshold;
-  lowp float pad2;
-};
+#include "common/noise_uniforms.glsl"
 
 in vec3 position;
 
@@ -24,10 +24,14 @@ uniform float uLineFadeOutLength;
 uniform float uMaxLineVelocity;
 uniform float uAdjustAdvection;
 uniform float uAdvectionDirection;
-uniform mediump vec4 uColorWheel[6];
+uniform mediump vec4 uColorWheel[MAX_COLOR_STOPS];
+uniform int uColorCount;
+// 0: sRGB, 1: linear RGB, 2: OKLab, 3: OKLCH
+uniform int uColorInterpolation;
 uniform mat4 uProjection;
 
 uniform sampler2D velocityTexture;
+uniform sampler2D obstacleTexture;
 
 // transform feedback output
 out vec2 vEndpointVector;
@@ -50,16 +54,68 @@ float clampTo(float value, float max) {
   return min(current, max) / current;
 }
 
-vec3 getColor(vec4 wheel[6], float angle) {
-  float slice = 2.0 * PI / 6.0;
+vec3 linearToSrgb(vec3 color) {
+  vec3 low = color * 12.92;
+  vec3 high = 1.055 * pow(color, vec3(1.0 / 2.4)) - 0.055;
+  return mix(high, low, vec3(lessThanEqual(color, vec3(0.0031308))));
+}
+
+// https://bottosson.github.io/posts/oklab/
+vec3 oklabToLinear(vec3 lab) {
+  float l = lab.x + 0.3963377774 * lab.y + 0.2158037573 * lab.z;
+  float m = lab.x - 0.1055613458 * lab.y - 0.0638541728 * lab.z;
+  float s = lab.x - 0.0894841775 * lab.y - 1.2914855480 * lab.z;
+
+  l = l * l * l;
+  m = m * m * m;
+  s = s * s * s;
+
+  return vec3(
+     4.0767416621 * l - 3.3077115913 * m + 0.2309699292 * s,
+    -1.2684380046 * l + 2.6097574011 * m - 0.3413193965 * s,
+    -0.0041960863 * l - 0.7034186147 * m + 1.7076147010 * s
+  );
+}
+
+// The color wheel is uploaded in the interpolation space. Blend there, and
+// convert the result back to sRGB.
+vec3 mixColors(vec3 currentColor, vec3 nextColor, float interpolate) {
+  if (uColorInterpolation == 1) {
+    return linearToSrgb(clamp(mix(currentColor, nextColor, interpolate), 0.0, 1.0));
+  }
+
+  if (uColorInterpolation == 2) {
+    vec3 lab = mix(currentColor, nextColor, interpolate);
+    return linearToSrgb(clamp(oklabToLinear(lab), 0.0, 1.0));
+  }
+
+  if (uColorInterpolation == 3) {
+    // Take the shortest path around the hue circle
+    float hueDelta = nextColor.z - currentColor.z;
+    hueDelta -= 2.0 * PI * floor((hueDelta + PI) / (2.0 * PI));
+
+    vec3 lch = vec3(
+      mix(currentColor.xy, nextColor.xy, interpolate),
+      currentColor.z + hueDelta * interpolate
+    );
+    vec3 lab = vec3(lch.x, lch.y * cos(lch.z), lch.y * sin(lch.z));
+    return linearToSrgb(clamp(oklabToLinear(lab), 0.0, 1.0));
+  }
+
+  return mix(currentColor, nextColor, interpolate);
+}
+
+vec3 getColor(float angle) {
+  float colorCount = float(uColorCount);
+  float slice = 2.0 * PI / colorCount;
   float rawIndex = angle / slice;
-  float index = floor(rawIndex);
-  float nextIndex = mod(index + 1.0, 6.0);
+  float index = min(floor(rawIndex), colorCount - 1.0);
+  float nextIndex = mod(index + 1.0, colorCount);
   float interpolate = fract(rawIndex);
 
-  vec3 currentColor = wheel[int(index)].rgb;
-  vec3 nextColor = wheel[int(nextIndex)].rgb;
-  return mix(currentColor, nextColor, interpolate);
+  vec3 currentColor = uColorWheel[int(index)].rgb;
+  vec3 nextColor = uColorWheel[int(nextIndex)].rgb;
+  return mixColors(currentColor, nextColor, interpolate);
 }
 
 float springForce(float stiffness, float displacement,  float damping, float velocity, float mass) {
@@ -96,7 +152,8 @@ void main() {
 
   // Velocity
   vec2 basepointInClipSpace = (uProjection * vec4(basepoint, 0.0, 1.0)).xy;
-  vec2 currentVelocityVector = texture(velocityTexture, basepointInClipSpace * 0.5 + 0.5).xy;
+  vec2 basepointInFluidSpace = basepointInClipSpace * 0.5 + 0.5;
+  vec2 currentVelocityVector = readField(velocityTexture, basepointInFluidSpace).xy;
   vec2 deltaVelocity = currentVelocityVector - iVelocityVector;
 
   float mass = uSpringMass * (1.0 + uSpringVariance * random1f(basepoint));
@@ -128,7 +185,7 @@ void main() {
     PI / 4.0 * currentLength + (PI + atan(iEndpointVector.y, iEndpointVector.x)),
     2.0 * PI
   );
-  vec4 newColor = vec4(getColor(uColorWheel, angle), 0.0);
+  vec4 newColor = vec4(getColor(angle), 0.0);
   vec4 colorDiff = newColor - iColor;
   vColor = clamp(
     iColor + colorDiff * deltaT,
@@ -150,4 +207,10 @@ void main() {
   // Opacity
   vLineOpacity = smoothstep(uLineFadeOutLength, 1.0, currentLength);
   vEndpointOpacity = endpointCurve(currentLength, iLineOpacity, 0.8);
+
+  // Hide the lines growing out of obstacles
+  if (texture(obstacleTexture, basepointInFluidSpace).x > 0.5) {
+    vLineOpacity = 0.0;
+    vEndpointOpacity = 0.0;
+  }
 }
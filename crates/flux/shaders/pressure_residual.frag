@@ -0,0 +1,48 @@
+precision highp float;
+precision highp sampler2D;
+
+#include "common/fluid_uniforms.glsl"
+
+uniform float alpha;
+uniform sampler2D divergenceTexture;
+uniform sampler2D pressureTexture;
+uniform sampler2D obstacleTexture;
+
+in vec2 textureCoord;
+in vec2 vL;
+in vec2 vR;
+in vec2 vT;
+in vec2 vB;
+FIELD_OUTPUT(residual);
+
+// Coarser multigrid levels sample the full-size mask, so `isSolid` filters
+// it and rounds.
+#include "common/obstacles.glsl"
+
+// How far the pressure is from solving the Poisson equation, with `alpha` set
+// to minus the squared grid size, like in `solve_pressure.frag`. The squared
+// residual goes in the second channel, ready to be averaged.
+void main() {
+  float L = readField(pressureTexture, vL).x;
+  float R = readField(pressureTexture, vR).x;
+  float T = readField(pressureTexture, vT).x;
+  float B = readField(pressureTexture, vB).x;
+  float C = readField(pressureTexture, textureCoord).x;
+  float divergence = readField(divergenceTexture, textureCoord).x;
+
+  // The pressure is zero past open edges.
+  if (boundary == OPEN) {
+    if (vL.x < 0.0) { L = 0.0; }
+    if (vR.x > 1.0) { R = 0.0; }
+    if (vT.y > 1.0) { T = 0.0; }
+    if (vB.y < 0.0) { B = 0.0; }
+  }
+
+  if (isSolid(vL)) { L = C; }
+  if (isSolid(vR)) { R = C; }
+  if (isSolid(vT)) { T = C; }
+  if (isSolid(vB)) { B = C; }
+
+  float r = isSolid(textureCoord) ? 0.0 : divergence + (L + R + B + T - 4.0 * C) / alpha;
+  residual = vec2(r, r * r);
+}
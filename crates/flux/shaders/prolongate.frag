@@ -0,0 +1,17 @@
+precision highp float;
+precision highp sampler2D;
+
+uniform sampler2D pressureTexture;
+uniform sampler2D correctionTexture;
+
+in vec2 textureCoord;
+FIELD_OUTPUT(newPressure);
+
+// Add the correction from a coarser grid, linearly interpolated up to the size
+// of this one.
+void main() {
+  vec2 pressure = readField(pressureTexture, textureCoord).xy;
+  vec2 correction = readField(correctionTexture, textureCoord).xy;
+
+  newPressure = pressure + correction;
+}
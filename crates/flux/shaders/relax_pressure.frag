@@ -0,0 +1,50 @@
+precision highp float;
+precision highp sampler2D;
+
+#include "common/fluid_uniforms.glsl"
+
+uniform float rBeta;
+uniform float alpha;
+uniform float omega;
+uniform sampler2D divergenceTexture;
+uniform sampler2D pressureTexture;
+uniform sampler2D obstacleTexture;
+
+in vec2 textureCoord;
+in vec2 vL;
+in vec2 vR;
+in vec2 vT;
+in vec2 vB;
+FIELD_OUTPUT(newPressure);
+
+// Coarser multigrid levels sample the full-size mask, so `isSolid` filters
+// it and rounds.
+#include "common/obstacles.glsl"
+
+// A weighted Jacobi iteration. Plain Jacobi never damps the checkerboard
+// pattern, which the coarser multigrid levels can’t see, so only move `omega`
+// of the way to the new value.
+void main() {
+  float L = readField(pressureTexture, vL).x;
+  float R = readField(pressureTexture, vR).x;
+  float T = readField(pressureTexture, vT).x;
+  float B = readField(pressureTexture, vB).x;
+  float C = readField(pressureTexture, textureCoord).x;
+  float divergence = readField(divergenceTexture, textureCoord).x;
+
+  // The pressure is zero past open edges.
+  if (boundary == OPEN) {
+    if (vL.x < 0.0) { L = 0.0; }
+    if (vR.x > 1.0) { R = 0.0; }
+    if (vT.y > 1.0) { T = 0.0; }
+    if (vB.y < 0.0) { B = 0.0; }
+  }
+
+  if (isSolid(vL)) { L = C; }
+  if (isSolid(vR)) { R = C; }
+  if (isSolid(vT)) { T = C; }
+  if (isSolid(vB)) { B = C; }
+
+  float jacobi = rBeta * (L + R + B + T + alpha * divergence);
+  newPressure = vec2(mix(C, jacobi, omega), 0.0);
+}
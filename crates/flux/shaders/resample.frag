@@ -0,0 +1,25 @@
+precision highp float;
+precision highp sampler2D;
+
+uniform sampler2D inputTexture;
+
+in vec2 textureCoord;
+FIELD_OUTPUT(newField);
+
+vec2 fetchField(ivec2 texel) {
+  ivec2 size = textureSize(inputTexture, 0);
+  return unpackField(texelFetch(inputTexture, clamp(texel, ivec2(0), size - 1), 0));
+}
+
+// Some GPUs filter 8-bit textures with only 8 bits of precision, which isn’t
+// enough to unpack the filtered bytes. So fetch the four nearest texels and
+// filter the unpacked values instead.
+void main() {
+  vec2 position = textureCoord * vec2(textureSize(inputTexture, 0)) - 0.5;
+  ivec2 corner = ivec2(floor(position));
+  vec2 weight = fract(position);
+
+  vec2 bottom = mix(fetchField(corner), fetchField(corner + ivec2(1, 0)), weight.x);
+  vec2 top = mix(fetchField(corner + ivec2(0, 1)), fetchField(corner + ivec2(1, 1)), weight.x);
+  newField = mix(bottom, top, weight.y);
+}
@@ -1,24 +1,45 @@
 precision mediump float;
 precision highp sampler2D;
 
+#include "common/fluid_uniforms.glsl"
+
 uniform float rBeta;
 uniform float alpha;
+// Only the pressure is zero past open edges. The velocity carries on.
+uniform bool zeroPastOpenEdges;
 uniform sampler2D divergenceTexture;
 uniform sampler2D pressureTexture;
+uniform sampler2D obstacleTexture;
 
 in vec2 textureCoord;
 in vec2 vL;
 in vec2 vR;
 in vec2 vT;
 in vec2 vB;
-out vec2 newPressure;
+FIELD_OUTPUT(newPressure);
+
+#include "common/obstacles.glsl"
 
 void main() {
-  vec2 L = texture(pressureTexture, vL).xy;
-  vec2 R = texture(pressureTexture, vR).xy;
-  vec2 T = texture(pressureTexture, vT).xy;
-  vec2 B = texture(pressureTexture, vB).xy;
-  vec2 divergence = texture(divergenceTexture, textureCoord).xy;
+  vec2 L = readField(pressureTexture, vL).xy;
+  vec2 R = readField(pressureTexture, vR).xy;
+  vec2 T = readField(pressureTexture, vT).xy;
+  vec2 B = readField(pressureTexture, vB).xy;
+  vec2 divergence = readField(divergenceTexture, textureCoord).xy;
+
+  if (boundary == OPEN && zeroPastOpenEdges) {
+    if (vL.x < 0.0) { L = vec2(0.0); }
+    if (vR.x > 1.0) { R = vec2(0.0); }
+    if (vT.y > 1.0) { T = vec2(0.0); }
+    if (vB.y < 0.0) { B = vec2(0.0); }
+  }
+
+  // No flow into obstacles, so there’s no pressure difference across them.
+  vec2 C = readField(pressureTexture, textureCoord).xy;
+  if (isSolid(vL)) { L = C; }
+  if (isSolid(vR)) { R = C; }
+  if (isSolid(vT)) { T = C; }
+  if (isSolid(vB)) { B = C; }
 
   newPressure = rBeta * (L + R + B + T + alpha * divergence);
 }
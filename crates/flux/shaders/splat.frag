@@ -0,0 +1,27 @@
+precision highp float;
+precision highp sampler2D;
+
+#include "common/fluid_uniforms.glsl"
+
+uniform sampler2D velocityTexture;
+uniform vec2 uPosition;
+uniform vec2 uForce;
+uniform float uRadius;
+uniform float uVortexStrength;
+
+in vec2 textureCoord;
+FIELD_OUTPUT(newVelocity);
+
+// Add a Gaussian splat of velocity around `uPosition`. The force pushes the
+// fluid in a single direction, while the vortex strength spins it around the
+// center, counterclockwise for positive values.
+void main() {
+  // Measure distances in units of the fluid height, so splats stay round.
+  vec2 aspect = vec2(uTexelSize.y / uTexelSize.x, 1.0);
+  vec2 offset = (textureCoord - uPosition) * aspect;
+  float falloff = exp(-dot(offset, offset) / (uRadius * uRadius));
+
+  vec2 swirl = vec2(-offset.y, offset.x) / uRadius;
+  vec2 velocity = readField(velocityTexture, textureCoord).xy;
+  newVelocity = velocity + falloff * (uForce + uVortexStrength * swirl);
+}
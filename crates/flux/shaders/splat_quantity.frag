@@ -0,0 +1,26 @@
+precision highp float;
+precision highp sampler2D;
+
+#include "common/fluid_uniforms.glsl"
+
+uniform sampler2D quantityTexture;
+uniform vec2 uPosition;
+uniform float uRadius;
+uniform float uAmount;
+// What to add, per unit of `uAmount`. For dye, this is the color, with a
+// density of 1 in the alpha channel.
+uniform vec4 uValue;
+
+in vec2 textureCoord;
+out vec4 newQuantity;
+
+// Add a Gaussian splat of a quantity around `uPosition`, the same shape as the
+// velocity splats in `splat.frag`.
+void main() {
+  vec2 aspect = vec2(uTexelSize.y / uTexelSize.x, 1.0);
+  vec2 offset = (textureCoord - uPosition) * aspect;
+  float falloff = exp(-dot(offset, offset) / (uRadius * uRadius));
+
+  vec4 quantity = texture(quantityTexture, textureCoord);
+  newQuantity = quantity + falloff * uAmount * uValue;
+}
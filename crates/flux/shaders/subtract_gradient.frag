@@ -1,34 +1,46 @@
 precision mediump float;
 precision highp sampler2D;
 
-layout(std140) uniform FluidUniforms
-{
-  highp float deltaT;
-  highp float epsilon;
-  highp float halfEpsilon;
-  highp float dissipation;
-  highp vec2 uTexelSize;
-  lowp float pad1;
-  lowp float pad2;
-};
+#include "common/fluid_uniforms.glsl"
 
 uniform sampler2D velocityTexture;
 uniform sampler2D pressureTexture;
+uniform sampler2D obstacleTexture;
 
 in vec2 textureCoord;
 in vec2 vL;
 in vec2 vR;
 in vec2 vT;
 in vec2 vB;
-out vec2 newVelocity;
+FIELD_OUTPUT(newVelocity);
+
+#include "common/obstacles.glsl"
 
 void main() {
-  vec2 velocity = texture(velocityTexture, textureCoord).xy;
+  vec2 velocity = readField(velocityTexture, textureCoord).xy;
+
+  float L = readField(pressureTexture, vL).x;
+  float R = readField(pressureTexture, vR).x;
+  float T = readField(pressureTexture, vT).x;
+  float B = readField(pressureTexture, vB).x;
 
-  float L = texture(pressureTexture, vL).x;
-  float R = texture(pressureTexture, vR).x;
-  float T = texture(pressureTexture, vT).x;
-  float B = texture(pressureTexture, vB).x;
+  // The pressure is zero past open edges.
+  if (boundary == OPEN) {
+    if (vL.x < 0.0) { L = 0.0; }
+    if (vR.x > 1.0) { R = 0.0; }
+    if (vT.y > 1.0) { T = 0.0; }
+    if (vB.y < 0.0) { B = 0.0; }
+  }
+
+  float C = readField(pressureTexture, textureCoord).x;
+  if (isSolid(vL)) { L = C; }
+  if (isSolid(vR)) { R = C; }
+  if (isSolid(vT)) { T = C; }
+  if (isSolid(vB)) { B = C; }
 
   newVelocity = velocity - halfEpsilon * vec2(R - L, T - B);
+
+  if (isSolid(textureCoord) || (boundary == NO_SLIP && isEdge(textureCoord))) {
+    newVelocity = vec2(0.0);
+  }
 }
@@ -0,0 +1,43 @@
+precision highp float;
+
+#include "common/noise_uniforms.glsl"
+
+uniform vec2 uResolution;
+
+FIELD_OUTPUT(noise);
+
+#include "common/hash.glsl"
+
+// Trilinearly interpolated random values on the integer lattice, remapped to
+// the range of the other noise kinds.
+float vnoise(vec3 p) {
+  vec3 i = floor(p);
+  vec3 f = fract(p);
+  vec3 u = f * f * (3.0 - 2.0 * f);
+
+  float n000 = hash33(i).x;
+  float n100 = hash33(i + vec3(1.0, 0.0, 0.0)).x;
+  float n010 = hash33(i + vec3(0.0, 1.0, 0.0)).x;
+  float n110 = hash33(i + vec3(1.0, 1.0, 0.0)).x;
+  float n001 = hash33(i + vec3(0.0, 0.0, 1.0)).x;
+  float n101 = hash33(i + vec3(1.0, 0.0, 1.0)).x;
+  float n011 = hash33(i + vec3(0.0, 1.0, 1.0)).x;
+  float n111 = hash33(i + vec3(1.0, 1.0, 1.0)).x;
+
+  float n = mix(
+    mix(mix(n000, n100, u.x), mix(n010, n110, u.x), u.y),
+    mix(mix(n001, n101, u.x), mix(n011, n111, u.x), u.y),
+    u.z
+  );
+  return 2.0 * n - 1.0;
+}
+
+void main() {
+  vec2 st = gl_FragCoord.xy / uResolution.xy;
+  st.x *= uResolution.x / uResolution.y;
+
+  float sx = vnoise(vec3(st * uFrequency, uOffset1));
+  float sy = vnoise(vec3(st * uFrequency, uOffset2));
+
+  noise = vec2(sx, sy);
+}
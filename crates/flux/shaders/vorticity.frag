@@ -0,0 +1,33 @@
+precision highp float;
+precision highp sampler2D;
+
+#include "common/fluid_uniforms.glsl"
+
+uniform sampler2D velocityTexture;
+uniform sampler2D curlTexture;
+uniform float uVorticityStrength;
+
+in vec2 textureCoord;
+in vec2 vL;
+in vec2 vR;
+in vec2 vT;
+in vec2 vB;
+FIELD_OUTPUT(newVelocity);
+
+// Push the fluid along the gradient of the curl magnitude, towards the centre
+// of each swirl, to make up for the eddies lost to numerical dissipation.
+void main() {
+  float L = readField(curlTexture, vL).x;
+  float R = readField(curlTexture, vR).x;
+  float T = readField(curlTexture, vT).x;
+  float B = readField(curlTexture, vB).x;
+  float C = readField(curlTexture, textureCoord).x;
+
+  vec2 force = vec2(abs(T) - abs(B), abs(R) - abs(L));
+  force /= length(force) + 0.0001;
+  force *= uVorticityStrength * C;
+  force.y *= -1.0;
+
+  vec2 velocity = readField(velocityTexture, textureCoord).xy;
+  newVelocity = velocity + deltaT * force;
+}
@@ -0,0 +1,40 @@
+precision highp float;
+
+#include "common/noise_uniforms.glsl"
+
+uniform vec2 uResolution;
+uniform float uJitter;
+
+FIELD_OUTPUT(noise);
+
+#include "common/hash.glsl"
+
+// Distance to the closest feature point, with one point per lattice cell,
+// remapped to the range of the other noise kinds.
+float worley(vec3 p) {
+  vec3 i = floor(p);
+  vec3 f = fract(p);
+  float closest = 1.0;
+
+  for (int z = -1; z <= 1; z++) {
+    for (int y = -1; y <= 1; y++) {
+      for (int x = -1; x <= 1; x++) {
+        vec3 cell = vec3(float(x), float(y), float(z));
+        vec3 point = 0.5 + uJitter * (hash33(i + cell) - 0.5);
+        closest = min(closest, length(cell + point - f));
+      }
+    }
+  }
+
+  return 2.0 * closest - 1.0;
+}
+
+void main() {
+  vec2 st = gl_FragCoord.xy / uResolution.xy;
+  st.x *= uResolution.x / uResolution.y;
+
+  float sx = worley(vec3(st * uFrequency, uOffset1));
+  float sy = worley(vec3(st * uFrequency, uOffset2));
+
+  noise = vec2(sx, sy);
+}
@@ -0,0 +1,925 @@
+use crate::{fluid, obstacle, settings};
+use obstacle::ObstacleMask;
+use settings::{AdvectionScheme, Boundary, PressureSolver, Settings};
+
+use std::rc::Rc;
+
+// A CPU port of the fluid solver in `fluid.rs`.
+//
+// Every pass mirrors its fragment shader, down to the texture wrap modes and
+// the ping-ponging of the double framebuffers, so the results can be used to
+// check the GPU path. It’s also handy for running the simulation without a GL
+// context.
+pub struct CpuFluid {
+    settings: Rc<Settings>,
+
+    pub width: u32,
+    pub height: u32,
+    texel_size: [f32; 2],
+    grid_size: f32,
+    timestep: f32,
+
+    velocity: Vec<[f32; 2]>,
+    curl: Vec<[f32; 2]>,
+    divergence: Vec<[f32; 2]>,
+    pressure: Vec<[f32; 2]>,
+    dye: Vec<[f32; 4]>,
+    temperature: Vec<[f32; 2]>,
+    obstacles: Vec<[f32; 2]>,
+    obstacle_mask: Option<ObstacleMask>,
+}
+
+// The obstacle mask, along with its size, so that coarser grids can sample it
+// like `isSolid` in the shaders.
+#[derive(Clone, Copy)]
+struct Obstacles<'a> {
+    mask: &'a [[f32; 2]],
+    width: u32,
+    height: u32,
+    edges: Edges,
+}
+
+// A grid of the pressure solve, or one of its coarser multigrid levels: its
+// size, what lookups past its edges return, and the obstacles inside it.
+#[derive(Clone, Copy)]
+struct Grid<'a> {
+    width: u32,
+    height: u32,
+    edges: Edges,
+    obstacles: Obstacles<'a>,
+}
+
+// What a lookup past the edge of a grid returns. `Clamp` and `Repeat` mirror
+// the texture wrap modes, and `Zero` mirrors the open edge checks in the
+// pressure shaders.
+#[derive(Clone, Copy, PartialEq)]
+enum Edges {
+    Clamp,
+    Repeat,
+    Zero,
+}
+
+impl CpuFluid {
+    pub fn new(ratio: f32, settings: &Rc<Settings>) -> Self {
+        let grid_size: f32 = 1.0;
+        let (width, height, texel_size) =
+            fluid::compute_fluid_size(settings.fluid_size as f32, ratio);
+        let cell_count = (width * height) as usize;
+
+        Self {
+            settings: Rc::clone(settings),
+
+            width,
+            height,
+            texel_size,
+            grid_size,
+            timestep: 0.0,
+
+            velocity: vec![[0.0; 2]; cell_count],
+            curl: vec![[0.0; 2]; cell_count],
+            divergence: vec![[0.0; 2]; cell_count],
+            pressure: vec![[0.0; 2]; cell_count],
+            dye: vec![[0.0; 4]; cell_count],
+            temperature: vec![[0.0; 2]; cell_count],
+            obstacles: obstacle::rasterize(&settings.obstacles, None, width, height),
+            obstacle_mask: None,
+        }
+    }
+
+    pub fn update(&mut self, settings: &Rc<Settings>) -> () {
+        self.settings = Rc::clone(settings);
+        self.rasterize_obstacles();
+
+        if settings.dye.is_none() {
+            self.dye.fill([0.0; 4]);
+        }
+        if settings.temperature.is_none() {
+            self.temperature.fill([0.0; 2]);
+        }
+    }
+
+    pub fn set_obstacle_mask(&mut self, mask: Option<ObstacleMask>) -> () {
+        self.obstacle_mask = mask;
+        self.rasterize_obstacles();
+    }
+
+    fn rasterize_obstacles(&mut self) -> () {
+        self.obstacles = obstacle::rasterize(
+            &self.settings.obstacles,
+            self.obstacle_mask.as_ref(),
+            self.width,
+            self.height,
+        );
+    }
+
+    pub fn resize(&mut self, ratio: f32) -> () {
+        let (width, height, texel_size) =
+            fluid::compute_fluid_size(self.settings.fluid_size as f32, ratio);
+
+        // Resample the old contents, like the blit in `Fluid::resize`.
+        self.velocity = resample(&self.velocity, self.width, self.height, width, height);
+        self.curl = resample(&self.curl, self.width, self.height, width, height);
+        self.divergence = resample(&self.divergence, self.width, self.height, width, height);
+        self.pressure = resample(&self.pressure, self.width, self.height, width, height);
+        self.dye = resample(&self.dye, self.width, self.height, width, height);
+        self.temperature = resample(&self.temperature, self.width, self.height, width, height);
+
+        self.width = width;
+        self.height = height;
+        self.texel_size = texel_size;
+        self.rasterize_obstacles();
+    }
+
+    // Run a full simulation step, in the same order as `Flux::animate`.
+    pub fn step(&mut self, timestep: f32) -> () {
+        self.prepare_pass(timestep);
+        self.advect();
+        self.diffuse(timestep);
+        self.apply_buoyancy();
+        self.confine_vorticity();
+        self.calculate_divergence();
+        self.solve_pressure();
+        self.subtract_gradient();
+        self.advect_dye();
+        self.advect_temperature();
+    }
+
+    pub fn prepare_pass(&mut self, timestep: f32) -> () {
+        self.timestep = timestep;
+    }
+
+    pub fn advect(&mut self) -> () {
+        self.velocity = match self.settings.advection_scheme {
+            AdvectionScheme::SemiLagrangian => self.advect_with(&self.velocity, 1.0, false),
+
+            AdvectionScheme::MacCormack => {
+                let (forward, backward) = self.trace_round_trip();
+                let decay = 1.0 + self.settings.velocity_dissipation * self.timestep;
+                let mut new_velocity = vec![[0.0; 2]; self.velocity.len()];
+
+                for y in 0..self.height {
+                    for x in 0..self.width {
+                        let index = self.index(x, y);
+                        if self.obstacles[index][0] > 0.5 || self.is_no_slip_edge(x, y) {
+                            continue;
+                        }
+
+                        let velocity = self.velocity[index];
+                        let corrected = [0, 1].map(|i| {
+                            forward[index][i] * decay + 0.5 * (velocity[i] - backward[index][i])
+                        });
+                        let past_coord = self.past_coord(x, y, 1.0);
+                        let limited = clamp_to_neighbours(
+                            corrected,
+                            &self.velocity,
+                            self.width,
+                            self.height,
+                            past_coord,
+                            self.velocity_edges(),
+                        );
+
+                        new_velocity[index] = limited.map(|component| component / decay);
+                    }
+                }
+
+                new_velocity
+            }
+
+            AdvectionScheme::Bfecc => {
+                let (_, backward) = self.trace_round_trip();
+                let corrected: Vec<[f32; 2]> = self
+                    .velocity
+                    .iter()
+                    .zip(backward)
+                    .map(|(velocity, backward)| {
+                        [0, 1].map(|i| velocity[i] + 0.5 * (velocity[i] - backward[i]))
+                    })
+                    .collect();
+
+                self.advect_with(&corrected, 1.0, true)
+            }
+        };
+    }
+
+    // A semi-Lagrangian step forward, and the same step traced back again, as
+    // in `Fluid::trace_round_trip`.
+    fn trace_round_trip(&self) -> (Vec<[f32; 2]>, Vec<[f32; 2]>) {
+        let forward = self.advect_with(&self.velocity, 1.0, false);
+        let backward = self.advect_with(&forward, -1.0, false);
+        (forward, backward)
+    }
+
+    // Move `input` along the velocity, as in `advection.frag`.
+    fn advect_with(&self, input: &[[f32; 2]], direction: f32, limit: bool) -> Vec<[f32; 2]> {
+        let decay = (1.0 + self.settings.velocity_dissipation * self.timestep).powf(direction);
+        let mut output = vec![[0.0; 2]; input.len()];
+
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let index = self.index(x, y);
+                if self.obstacles[index][0] > 0.5 || self.is_no_slip_edge(x, y) {
+                    continue;
+                }
+
+                let past_coord = self.past_coord(x, y, direction);
+                let mut past_velocity = sample(
+                    input,
+                    self.width,
+                    self.height,
+                    past_coord,
+                    self.velocity_edges(),
+                );
+                if limit {
+                    past_velocity = clamp_to_neighbours(
+                        past_velocity,
+                        &self.velocity,
+                        self.width,
+                        self.height,
+                        past_coord,
+                        self.velocity_edges(),
+                    );
+                }
+
+                output[index] = past_velocity.map(|component| component / decay);
+            }
+        }
+
+        output
+    }
+
+    // Where the fluid in a cell was a step ago, or will be a step from now if
+    // `direction` is negative.
+    fn past_coord(&self, x: u32, y: u32, direction: f32) -> [f32; 2] {
+        let velocity = self.velocity[self.index(x, y)];
+        let [u, v] = self.texture_coord(x, y);
+        let distance = direction * self.grid_size * self.timestep;
+
+        [u - distance * velocity[0], v - distance * velocity[1]]
+    }
+
+    pub fn diffuse(&mut self, timestep: f32) -> () {
+        let center_factor = self.grid_size.powf(2.0) / (self.settings.viscosity * timestep);
+        let stencil_factor = 1.0 / (4.0 + center_factor);
+
+        // The GPU pass binds the current velocity to both samplers, so the
+        // right-hand side is the previous iterate, not the initial velocity.
+        for _ in 0..self.settings.diffusion_iterations {
+            self.velocity = jacobi(
+                &self.velocity,
+                &self.velocity,
+                self.grid(self.velocity_edges()),
+                center_factor,
+                stencil_factor,
+            );
+        }
+    }
+
+    pub fn confine_vorticity(&mut self) -> () {
+        if self.settings.vorticity_strength <= 0.0 {
+            return;
+        }
+
+        let half_epsilon = 0.5 * self.grid_size;
+
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let [l, r, t, b] = neighbours(
+                    &self.velocity,
+                    self.width,
+                    self.height,
+                    x,
+                    y,
+                    self.velocity_edges(),
+                );
+                let index = self.index(x, y);
+                self.curl[index] = [half_epsilon * (r[1] - l[1] - t[0] + b[0]), 0.0];
+            }
+        }
+
+        let mut new_velocity = vec![[0.0; 2]; self.velocity.len()];
+
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let [l, r, t, b] = neighbours(
+                    &self.curl,
+                    self.width,
+                    self.height,
+                    x,
+                    y,
+                    self.velocity_edges(),
+                );
+                let index = self.index(x, y);
+                let curl = self.curl[index][0];
+
+                let force = [t[0].abs() - b[0].abs(), r[0].abs() - l[0].abs()];
+                let length = (force[0] * force[0] + force[1] * force[1]).sqrt() + 0.0001;
+                let scale = self.settings.vorticity_strength * curl / length;
+
+                let velocity = self.velocity[index];
+                new_velocity[index] = [
+                    velocity[0] + self.timestep * scale * force[0],
+                    velocity[1] - self.timestep * scale * force[1],
+                ];
+            }
+        }
+
+        self.velocity = new_velocity;
+    }
+
+    pub fn calculate_divergence(&mut self) -> () {
+        let half_epsilon = 0.5 * self.grid_size;
+        let last_x = self.width - 1;
+        let last_y = self.height - 1;
+        let has_walls = matches!(
+            self.settings.boundary,
+            Boundary::FreeSlip | Boundary::NoSlip
+        );
+
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let velocity = self.velocity[self.index(x, y)];
+                let [l, r, t, b] = neighbours(
+                    &self.velocity,
+                    self.width,
+                    self.height,
+                    x,
+                    y,
+                    self.velocity_edges(),
+                );
+                let (l, r, t, b) = (l[0], r[0], t[1], b[1]);
+
+                // Reflect the velocity at solid walls.
+                let l = if has_walls && x == 0 { -velocity[0] } else { l };
+                let r = if has_walls && x == last_x {
+                    -velocity[0]
+                } else {
+                    r
+                };
+                let t = if has_walls && y == last_y {
+                    -velocity[1]
+                } else {
+                    t
+                };
+                let b = if has_walls && y == 0 { -velocity[1] } else { b };
+
+                // Reflect off obstacles, just like the walls.
+                let obstacles = self.get_obstacles();
+                let (is_solid, [l_solid, r_solid, t_solid, b_solid]) =
+                    obstacles.around(self.width, self.height, x, y);
+                let l = if l_solid { -velocity[0] } else { l };
+                let r = if r_solid { -velocity[0] } else { r };
+                let t = if t_solid { -velocity[1] } else { t };
+                let b = if b_solid { -velocity[1] } else { b };
+
+                let index = self.index(x, y);
+                self.divergence[index] = if is_solid {
+                    [0.0, 0.0]
+                } else {
+                    [half_epsilon * (r - l + t - b), 0.0]
+                };
+            }
+        }
+    }
+
+    pub fn solve_pressure(&mut self) -> () {
+        let alpha = -self.grid_size * self.grid_size;
+        let r_beta = 0.25;
+
+        if !self.settings.warm_start_pressure {
+            self.pressure.fill([self.settings.starting_pressure, 0.0]);
+        }
+
+        // Unlike `Fluid`, this checks for convergence with the exact residual,
+        // so it may stop at a slightly different iteration.
+        match self.settings.pressure_solver {
+            PressureSolver::Jacobi => {
+                for iteration in 1..=self.settings.pressure_iterations {
+                    self.pressure = jacobi(
+                        &self.divergence,
+                        &self.pressure,
+                        self.grid(self.pressure_edges()),
+                        alpha,
+                        r_beta,
+                    );
+
+                    if iteration % fluid::PRESSURE_CHECK_INTERVAL == 0
+                        && iteration < self.settings.pressure_iterations
+                        && self.has_pressure_converged()
+                    {
+                        break;
+                    }
+                }
+            }
+            PressureSolver::Multigrid {
+                cycles,
+                smoothing_iterations,
+            } => {
+                let levels =
+                    fluid::compute_multigrid_sizes(self.width, self.height, self.grid_size);
+
+                for cycle in 1..=cycles {
+                    self.pressure = v_cycle(
+                        &self.divergence,
+                        &self.pressure,
+                        self.grid(self.pressure_edges()),
+                        self.grid_size,
+                        &levels,
+                        smoothing_iterations,
+                    );
+
+                    if cycle < cycles && self.has_pressure_converged() {
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    fn has_pressure_converged(&self) -> bool {
+        self.settings.pressure_tolerance > 0.0
+            && self.pressure_residual() < self.settings.pressure_tolerance
+    }
+
+    // The root mean square of the residual of the last pressure solve, like
+    // `Fluid::pressure_residual`.
+    pub fn pressure_residual(&self) -> f32 {
+        let alpha = -self.grid_size * self.grid_size;
+        let residual = residual(
+            &self.divergence,
+            &self.pressure,
+            self.grid(self.pressure_edges()),
+            alpha,
+        );
+        let sum_of_squares: f32 = residual.iter().map(|cell| cell[1]).sum();
+        (sum_of_squares / residual.len() as f32).sqrt()
+    }
+
+    pub fn subtract_gradient(&mut self) -> () {
+        let half_epsilon = 0.5 * self.grid_size;
+        let mut new_velocity = vec![[0.0; 2]; self.velocity.len()];
+
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let (is_solid, [l, r, t, b]) =
+                    solid_neighbours(&self.pressure, self.grid(self.pressure_edges()), x, y);
+                let index = self.index(x, y);
+                let velocity = self.velocity[index];
+
+                new_velocity[index] = if is_solid || self.is_no_slip_edge(x, y) {
+                    [0.0, 0.0]
+                } else {
+                    [
+                        velocity[0] - half_epsilon * (r[0] - l[0]),
+                        velocity[1] - half_epsilon * (t[0] - b[0]),
+                    ]
+                };
+            }
+        }
+
+        self.velocity = new_velocity;
+    }
+
+    pub fn apply_buoyancy(&mut self) -> () {
+        let temperature = match &self.settings.temperature {
+            Some(temperature) => temperature,
+            None => return,
+        };
+
+        let lift = self.timestep * temperature.buoyancy;
+        for (velocity, temperature) in self.velocity.iter_mut().zip(self.temperature.iter()) {
+            velocity[1] += lift * temperature[0];
+        }
+    }
+
+    pub fn advect_dye(&mut self) -> () {
+        if let Some(dye) = &self.settings.dye {
+            self.dye = self.advect_quantity(&self.dye, dye.dissipation);
+        }
+    }
+
+    pub fn advect_temperature(&mut self) -> () {
+        if let Some(temperature) = &self.settings.temperature {
+            self.temperature = self.advect_quantity(&self.temperature, temperature.dissipation);
+        }
+    }
+
+    fn advect_quantity<const N: usize>(
+        &self,
+        quantity: &[[f32; N]],
+        dissipation: f32,
+    ) -> Vec<[f32; N]> {
+        let epsilon = self.grid_size;
+        let decay = 1.0 + dissipation * self.timestep;
+        let mut new_quantity = vec![[0.0; N]; quantity.len()];
+
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let index = self.index(x, y);
+                let velocity = self.velocity[index];
+                let [u, v] = self.texture_coord(x, y);
+                let past_coord = [
+                    u - epsilon * self.timestep * velocity[0],
+                    v - epsilon * self.timestep * velocity[1],
+                ];
+                let past_quantity = sample(
+                    quantity,
+                    self.width,
+                    self.height,
+                    past_coord,
+                    self.velocity_edges(),
+                );
+
+                if self.obstacles[index][0] <= 0.5 {
+                    new_quantity[index] = past_quantity.map(|channel| channel / decay);
+                }
+            }
+        }
+
+        new_quantity
+    }
+
+    pub fn get_velocity(&self) -> &[[f32; 2]] {
+        &self.velocity
+    }
+
+    pub fn get_velocity_mut(&mut self) -> &mut [[f32; 2]] {
+        &mut self.velocity
+    }
+
+    pub fn get_curl(&self) -> &[[f32; 2]] {
+        &self.curl
+    }
+
+    pub fn get_divergence(&self) -> &[[f32; 2]] {
+        &self.divergence
+    }
+
+    pub fn get_pressure(&self) -> &[[f32; 2]] {
+        &self.pressure
+    }
+
+    pub fn get_dye(&self) -> &[[f32; 4]] {
+        &self.dye
+    }
+
+    pub fn get_dye_mut(&mut self) -> &mut [[f32; 4]] {
+        &mut self.dye
+    }
+
+    pub fn get_temperature(&self) -> &[[f32; 2]] {
+        &self.temperature
+    }
+
+    pub fn get_temperature_mut(&mut self) -> &mut [[f32; 2]] {
+        &mut self.temperature
+    }
+
+    fn get_obstacles(&self) -> Obstacles<'_> {
+        Obstacles {
+            mask: &self.obstacles,
+            width: self.width,
+            height: self.height,
+            edges: self.velocity_edges(),
+        }
+    }
+
+    fn grid(&self, edges: Edges) -> Grid<'_> {
+        Grid {
+            width: self.width,
+            height: self.height,
+            edges,
+            obstacles: self.get_obstacles(),
+        }
+    }
+
+    // Lookups past the edges of the velocity, like the wrap mode set by
+    // `Fluid::set_texture_wrap`.
+    fn velocity_edges(&self) -> Edges {
+        match self.settings.boundary {
+            Boundary::Periodic => Edges::Repeat,
+            _ => Edges::Clamp,
+        }
+    }
+
+    fn pressure_edges(&self) -> Edges {
+        match self.settings.boundary {
+            Boundary::Open => Edges::Zero,
+            _ => self.velocity_edges(),
+        }
+    }
+
+    // Whether the fluid sticks to this cell, like `isEdge` in the shaders.
+    fn is_no_slip_edge(&self, x: u32, y: u32) -> bool {
+        self.settings.boundary == Boundary::NoSlip
+            && (x == 0 || y == 0 || x == self.width - 1 || y == self.height - 1)
+    }
+
+    pub fn get_texel_size(&self) -> [f32; 2] {
+        self.texel_size
+    }
+
+    // Sample the velocity field at a texture coordinate, with the same linear
+    // filtering the GPU uses.
+    pub fn sample_velocity(&self, texture_coord: [f32; 2]) -> [f32; 2] {
+        sample(
+            &self.velocity,
+            self.width,
+            self.height,
+            texture_coord,
+            self.velocity_edges(),
+        )
+    }
+
+    fn index(&self, x: u32, y: u32) -> usize {
+        (y * self.width + x) as usize
+    }
+
+    fn texture_coord(&self, x: u32, y: u32) -> [f32; 2] {
+        [
+            (x as f32 + 0.5) * self.texel_size[0],
+            (y as f32 + 0.5) * self.texel_size[1],
+        ]
+    }
+}
+
+// A single Jacobi iteration, as in `solve_pressure.frag`.
+fn jacobi(b: &[[f32; 2]], x: &[[f32; 2]], grid: Grid, alpha: f32, r_beta: f32) -> Vec<[f32; 2]> {
+    let mut output = vec![[0.0; 2]; x.len()];
+
+    for row in 0..grid.height {
+        for col in 0..grid.width {
+            let (_, [l, r, t, b_]) = solid_neighbours(x, grid, col, row);
+            let index = (row * grid.width + col) as usize;
+            let center = b[index];
+
+            output[index] = [
+                r_beta * (l[0] + r[0] + b_[0] + t[0] + alpha * center[0]),
+                r_beta * (l[1] + r[1] + b_[1] + t[1] + alpha * center[1]),
+            ];
+        }
+    }
+
+    output
+}
+
+// A weighted Jacobi iteration, as in `relax_pressure.frag`.
+fn relax(b: &[[f32; 2]], x: &[[f32; 2]], grid: Grid, alpha: f32, iterations: u32) -> Vec<[f32; 2]> {
+    let omega = fluid::MULTIGRID_SMOOTHING_WEIGHT;
+    let mut x = x.to_vec();
+
+    for _ in 0..iterations {
+        let next = jacobi(b, &x, grid, alpha, 0.25);
+        for (cell, next_cell) in x.iter_mut().zip(next) {
+            *cell = [cell[0] + omega * (next_cell[0] - cell[0]), 0.0];
+        }
+    }
+
+    x
+}
+
+// The residual of the pressure equation, and its square, as in
+// `pressure_residual.frag`.
+fn residual(b: &[[f32; 2]], x: &[[f32; 2]], grid: Grid, alpha: f32) -> Vec<[f32; 2]> {
+    let mut output = vec![[0.0; 2]; x.len()];
+
+    for row in 0..grid.height {
+        for col in 0..grid.width {
+            let (is_solid, [l, r, t, b_]) = solid_neighbours(x, grid, col, row);
+            let index = (row * grid.width + col) as usize;
+            let laplacian = l[0] + r[0] + b_[0] + t[0] - 4.0 * x[index][0];
+            let residual = if is_solid {
+                0.0
+            } else {
+                b[index][0] + laplacian / alpha
+            };
+
+            output[index] = [residual, residual * residual];
+        }
+    }
+
+    output
+}
+
+// A multigrid V-cycle, as in `Fluid::v_cycle`. `levels` are the sizes of the
+// coarser grids still to visit.
+fn v_cycle(
+    b: &[[f32; 2]],
+    x: &[[f32; 2]],
+    grid: Grid,
+    grid_size: f32,
+    levels: &[(u32, u32, [f32; 2], f32)],
+    smoothing_iterations: u32,
+) -> Vec<[f32; 2]> {
+    let alpha = -grid_size * grid_size;
+
+    let (&(coarse_width, coarse_height, _, coarse_grid_size), coarser_levels) =
+        match levels.split_first() {
+            Some(levels) => levels,
+            None => return relax(b, x, grid, alpha, fluid::COARSEST_MULTIGRID_ITERATIONS),
+        };
+    let coarse_grid = Grid {
+        width: coarse_width,
+        height: coarse_height,
+        ..grid
+    };
+
+    let x = relax(b, x, grid, alpha, smoothing_iterations);
+
+    // Restrict the residual with the same bilinear filtering as the blit.
+    let coarse_b = resample(
+        &residual(b, &x, grid, alpha),
+        grid.width,
+        grid.height,
+        coarse_width,
+        coarse_height,
+    );
+    let coarse_x = vec![[0.0; 2]; (coarse_width * coarse_height) as usize];
+    let correction = v_cycle(
+        &coarse_b,
+        &coarse_x,
+        coarse_grid,
+        coarse_grid_size,
+        coarser_levels,
+        smoothing_iterations,
+    );
+
+    let correction = resample(
+        &correction,
+        coarse_width,
+        coarse_height,
+        grid.width,
+        grid.height,
+    );
+    let x: Vec<[f32; 2]> = x
+        .iter()
+        .zip(correction)
+        .map(|(cell, correction)| [cell[0] + correction[0], cell[1] + correction[1]])
+        .collect();
+
+    relax(b, &x, grid, alpha, smoothing_iterations)
+}
+
+impl<'a> Obstacles<'a> {
+    // Whether the cell at (`x`, `y`) of a grid of the given size, and its left,
+    // right, top, and bottom neighbours, are inside obstacles.
+    fn around(&self, width: u32, height: u32, x: u32, y: u32) -> (bool, [bool; 4]) {
+        let is_solid = |x: f32, y: f32| {
+            let texture_coord = [(x + 0.5) / width as f32, (y + 0.5) / height as f32];
+            sample(
+                self.mask,
+                self.width,
+                self.height,
+                texture_coord,
+                self.edges,
+            )[0] > 0.5
+        };
+        let (x, y) = (x as f32, y as f32);
+
+        (
+            is_solid(x, y),
+            [
+                is_solid(x - 1.0, y),
+                is_solid(x + 1.0, y),
+                is_solid(x, y + 1.0),
+                is_solid(x, y - 1.0),
+            ],
+        )
+    }
+}
+
+// Like `neighbours`, but neighbours inside obstacles take the value of the
+// cell itself. Also returns whether the cell is inside an obstacle.
+fn solid_neighbours(values: &[[f32; 2]], grid: Grid, x: u32, y: u32) -> (bool, [[f32; 2]; 4]) {
+    let (is_solid, solid) = grid.obstacles.around(grid.width, grid.height, x, y);
+    let center = values[(y * grid.width + x) as usize];
+    let mut values = neighbours(values, grid.width, grid.height, x, y, grid.edges);
+
+    for (value, solid) in values.iter_mut().zip(solid) {
+        if solid {
+            *value = center;
+        }
+    }
+
+    (is_solid, values)
+}
+
+// Fetch the left, right, top, and bottom neighbours of a cell.
+fn neighbours(
+    grid: &[[f32; 2]],
+    width: u32,
+    height: u32,
+    x: u32,
+    y: u32,
+    edges: Edges,
+) -> [[f32; 2]; 4] {
+    let x = x as i32;
+    let y = y as i32;
+
+    [
+        fetch(grid, width, height, x - 1, y, edges),
+        fetch(grid, width, height, x + 1, y, edges),
+        fetch(grid, width, height, x, y + 1, edges),
+        fetch(grid, width, height, x, y - 1, edges),
+    ]
+}
+
+fn fetch<const N: usize>(
+    grid: &[[f32; N]],
+    width: u32,
+    height: u32,
+    x: i32,
+    y: i32,
+    edges: Edges,
+) -> [f32; N] {
+    let (w, h) = (width as i32, height as i32);
+    let is_outside = x < 0 || y < 0 || x >= w || y >= h;
+
+    let (x, y) = match edges {
+        Edges::Zero if is_outside => return [0.0; N],
+        Edges::Repeat => (x.rem_euclid(w), y.rem_euclid(h)),
+        _ => (x.clamp(0, w - 1), y.clamp(0, h - 1)),
+    };
+    grid[(y * w + x) as usize]
+}
+
+// Bilinear sampling, with the given wrapping.
+fn sample<const N: usize>(
+    grid: &[[f32; N]],
+    width: u32,
+    height: u32,
+    texture_coord: [f32; 2],
+    edges: Edges,
+) -> [f32; N] {
+    let u = texture_coord[0] * width as f32 - 0.5;
+    let v = texture_coord[1] * height as f32 - 0.5;
+    let x0 = u.floor();
+    let y0 = v.floor();
+    let fx = u - x0;
+    let fy = v - y0;
+    let x0 = x0 as i32;
+    let y0 = y0 as i32;
+
+    let c00 = fetch(grid, width, height, x0, y0, edges);
+    let c10 = fetch(grid, width, height, x0 + 1, y0, edges);
+    let c01 = fetch(grid, width, height, x0, y0 + 1, edges);
+    let c11 = fetch(grid, width, height, x0 + 1, y0 + 1, edges);
+
+    let mut output = [0.0; N];
+    for i in 0..N {
+        let bottom = c00[i] + (c10[i] - c00[i]) * fx;
+        let top = c01[i] + (c11[i] - c01[i]) * fx;
+        output[i] = bottom + (top - bottom) * fy;
+    }
+    output
+}
+
+// Clamp a value to the range of the four texels that bilinear sampling at
+// `texture_coord` would blend, as in `clampToNeighbours` in the shaders.
+fn clamp_to_neighbours(
+    value: [f32; 2],
+    grid: &[[f32; 2]],
+    width: u32,
+    height: u32,
+    texture_coord: [f32; 2],
+    edges: Edges,
+) -> [f32; 2] {
+    let x0 = (texture_coord[0] * width as f32 - 0.5).floor() as i32;
+    let y0 = (texture_coord[1] * height as f32 - 0.5).floor() as i32;
+    let corners = [
+        fetch(grid, width, height, x0, y0, edges),
+        fetch(grid, width, height, x0 + 1, y0, edges),
+        fetch(grid, width, height, x0, y0 + 1, edges),
+        fetch(grid, width, height, x0 + 1, y0 + 1, edges),
+    ];
+
+    [0, 1].map(|i| {
+        let min = corners.iter().map(|c| c[i]).fold(f32::INFINITY, f32::min);
+        let max = corners
+            .iter()
+            .map(|c| c[i])
+            .fold(f32::NEG_INFINITY, f32::max);
+        value[i].clamp(min, max)
+    })
+}
+
+fn resample<const N: usize>(
+    grid: &[[f32; N]],
+    width: u32,
+    height: u32,
+    new_width: u32,
+    new_height: u32,
+) -> Vec<[f32; N]> {
+    let mut output = Vec::with_capacity((new_width * new_height) as usize);
+
+    for y in 0..new_height {
+        for x in 0..new_width {
+            let texture_coord = [
+                (x as f32 + 0.5) / new_width as f32,
+                (y as f32 + 0.5) / new_height as f32,
+            ];
+            output.push(sample(grid, width, height, texture_coord, Edges::Clamp));
+        }
+    }
+
+    output
+}
@@ -1,8 +1,9 @@
 use crate::{data, render, settings};
 use render::{
-    Buffer, Context, Framebuffer, Uniform, UniformValue, VertexArrayObject, VertexBufferLayout,
+    Buffer, Capabilities, Context, Define, FieldFormat, Framebuffer, Uniform, UniformValue,
+    VertexArrayObject, VertexBufferLayout,
 };
-use settings::Settings;
+use settings::{Dye, DyeBlendMode, Settings};
 
 extern crate nalgebra_glm as glm;
 use bytemuck::{Pod, Zeroable};
@@ -10,22 +11,16 @@ use glow::HasContext;
 use std::f32::consts::PI;
 use std::rc::Rc;
 
-static LINE_VERT_SHADER: &'static str =
-    include_str!(concat!(env!("OUT_DIR"), "/shaders/line.vert"));
-static LINE_FRAG_SHADER: &'static str =
-    include_str!(concat!(env!("OUT_DIR"), "/shaders/line.frag"));
-static ENDPOINT_VERT_SHADER: &'static str =
-    include_str!(concat!(env!("OUT_DIR"), "/shaders/endpoint.vert"));
-static ENDPOINT_FRAG_SHADER: &'static str =
-    include_str!(concat!(env!("OUT_DIR"), "/shaders/endpoint.frag"));
-static TEXTURE_VERT_SHADER: &'static str =
-    include_str!(concat!(env!("OUT_DIR"), "/shaders/texture.vert"));
-static TEXTURE_FRAG_SHADER: &'static str =
-    include_str!(concat!(env!("OUT_DIR"), "/shaders/texture.frag"));
-static PLACE_LINES_VERT_SHADER: &'static str =
-    include_str!(concat!(env!("OUT_DIR"), "/shaders/place_lines.vert"));
-static PLACE_LINES_FRAG_SHADER: &'static str =
-    include_str!(concat!(env!("OUT_DIR"), "/shaders/place_lines.frag"));
+static LINE_VERT_SHADER: &'static str = include_str!("../shaders/line.vert");
+static LINE_FRAG_SHADER: &'static str = include_str!("../shaders/line.frag");
+static ENDPOINT_VERT_SHADER: &'static str = include_str!("../shaders/endpoint.vert");
+static ENDPOINT_FRAG_SHADER: &'static str = include_str!("../shaders/endpoint.frag");
+static TEXTURE_VERT_SHADER: &'static str = include_str!("../shaders/texture.vert");
+static TEXTURE_FRAG_SHADER: &'static str = include_str!("../shaders/texture.frag");
+static PLACE_LINES_VERT_SHADER: &'static str = include_str!("../shaders/place_lines.vert");
+static PLACE_LINES_FRAG_SHADER: &'static str = include_str!("../shaders/place_lines.frag");
+static DYE_VERT_SHADER: &'static str = include_str!("../shaders/dye.vert");
+static DYE_FRAG_SHADER: &'static str = include_str!("../shaders/dye.frag");
 
 #[rustfmt::skip]
 const LINE_VERTICES: [f32; 12] = [
@@ -48,6 +43,7 @@ struct LineState {
     endpoint_opacity: f32,
 }
 
+// Must match `shaders/common/projection.glsl`
 #[repr(C)]
 #[derive(Clone, Copy, Pod, Zeroable)]
 struct Projection {
@@ -55,6 +51,7 @@ struct Projection {
     view: [f32; 16],
 }
 
+// Must match `shaders/common/line_uniforms.glsl`
 #[repr(C)]
 #[derive(Clone, Copy, Pod, Zeroable)]
 struct LineUniforms {
@@ -75,10 +72,27 @@ impl LineUniforms {
     }
 }
 
+// The position, velocity and color of every line, as floats in the layout of
+// `LineState`.
+#[derive(Clone, Debug)]
+pub struct DrawerState {
+    pub line_count: u32,
+    pub line_state: Vec<f32>,
+}
+
+// What the drawer needs to know about the context. The field format may differ
+// from the one the capabilities suggest, if the caller forced another one.
+pub struct DrawerOptions<'a> {
+    pub capabilities: &'a Capabilities,
+    pub field_format: FieldFormat,
+}
+
 pub struct Drawer {
     context: Context,
     settings: Rc<Settings>,
 
+    logical_width: u32,
+    logical_height: u32,
     physical_width: u32,
     physical_height: u32,
 
@@ -96,6 +110,7 @@ pub struct Drawer {
     draw_lines_buffer: VertexArrayObject,
     draw_endpoints_buffer: VertexArrayObject,
     draw_texture_buffer: VertexArrayObject,
+    draw_dye_buffer: VertexArrayObject,
 
     view_buffer: Buffer,
     line_uniforms: Buffer,
@@ -104,6 +119,7 @@ pub struct Drawer {
     draw_lines_pass: render::Program,
     draw_endpoints_pass: render::Program,
     draw_texture_pass: render::Program,
+    draw_dye_pass: render::Program,
     antialiasing_pass: render::MsaaPass,
 }
 
@@ -115,6 +131,7 @@ impl Drawer {
         physical_width: u32,
         physical_height: u32,
         settings: &Rc<Settings>,
+        options: DrawerOptions,
     ) -> Result<Self, render::Problem> {
         let (grid_width, grid_height) = compute_grid_size(logical_width, logical_height);
 
@@ -132,6 +149,7 @@ impl Drawer {
                 .create_transform_feedback()
                 .map_err(|_| render::Problem::OutOfMemory)?
         };
+        render::track_created(1);
 
         let line_vertices = Buffer::from_f32(
             &context,
@@ -166,9 +184,12 @@ impl Drawer {
 
         // Programs
 
+        // Placing the lines reads the velocity field
+        let place_lines_vert_shader = options.field_format.prepare_shader(PLACE_LINES_VERT_SHADER);
         let place_lines_program = render::Program::new_with_transform_feedback(
             &context,
-            (PLACE_LINES_VERT_SHADER, PLACE_LINES_FRAG_SHADER),
+            (&place_lines_vert_shader, PLACE_LINES_FRAG_SHADER),
+            &[("MAX_COLOR_STOPS", settings::MAX_COLOR_STOPS.to_string())],
             &render::TransformFeedback {
                 // The order here must match the order in the buffer!
                 names: &[
@@ -183,11 +204,16 @@ impl Drawer {
             },
         )?;
         let draw_lines_program =
-            render::Program::new(&context, (LINE_VERT_SHADER, LINE_FRAG_SHADER))?;
-        let draw_endpoints_program =
-            render::Program::new(&context, (ENDPOINT_VERT_SHADER, ENDPOINT_FRAG_SHADER))?;
+            render::Program::new(&context, (LINE_VERT_SHADER, LINE_FRAG_SHADER), &[])?;
+        let draw_endpoints_program = render::Program::new(
+            &context,
+            (ENDPOINT_VERT_SHADER, ENDPOINT_FRAG_SHADER),
+            &blend_mode_defines(),
+        )?;
         let draw_texture_program =
-            render::Program::new(&context, (TEXTURE_VERT_SHADER, TEXTURE_FRAG_SHADER))?;
+            render::Program::new(&context, (TEXTURE_VERT_SHADER, TEXTURE_FRAG_SHADER), &[])?;
+        let draw_dye_program =
+            render::Program::new(&context, (DYE_VERT_SHADER, DYE_FRAG_SHADER), &[])?;
 
         // Vertex buffers
 
@@ -234,6 +260,20 @@ impl Drawer {
             )],
             Some(&plane_indices),
         )?;
+        let draw_dye_buffer = VertexArrayObject::new(
+            &context,
+            &draw_dye_program,
+            &[(
+                &plane_vertices,
+                VertexBufferLayout {
+                    name: "position",
+                    size: 3,
+                    type_: glow::FLOAT,
+                    ..Default::default()
+                },
+            )],
+            Some(&plane_indices),
+        )?;
 
         // Uniforms
 
@@ -272,12 +312,19 @@ impl Drawer {
         //
         // Safari on iOS crashes if you use a uniform block buffer together with
         // transform feedback.
-        let color_wheel = settings::color_wheel_from_scheme(&settings.color_scheme);
+        let color_wheel = settings::color_wheel_from_scheme(
+            &settings.color_scheme,
+            &settings.color_interpolation,
+        );
         place_lines_program.set_uniforms(&[
             &Uniform {
                 name: "velocityTexture",
                 value: UniformValue::Texture2D(0),
             },
+            &Uniform {
+                name: "obstacleTexture",
+                value: UniformValue::Texture2D(1),
+            },
             &Uniform {
                 name: "uLineFadeOutLength",
                 value: UniformValue::Float(settings.line_fade_out_length),
@@ -318,6 +365,14 @@ impl Drawer {
                 name: "uColorWheel[0]",
                 value: UniformValue::Vec4Array(&color_wheel),
             },
+            &Uniform {
+                name: "uColorCount",
+                value: UniformValue::SignedInt((color_wheel.len() / 4) as i32),
+            },
+            &Uniform {
+                name: "uColorInterpolation",
+                value: UniformValue::SignedInt(settings.color_interpolation.to_uniform()),
+            },
             &Uniform {
                 name: "uProjection",
                 value: UniformValue::Mat4(&projection_matrix.as_slice()),
@@ -329,19 +384,26 @@ impl Drawer {
         draw_endpoints_program.set_uniform_block("Projection", 0);
         draw_endpoints_program.set_uniform_block("LineUniforms", 1);
         draw_texture_program.set_uniform_block("Projection", 0);
+        draw_dye_program.set_uniform_block("Projection", 0);
+        draw_dye_program.set_uniform(&Uniform {
+            name: "dyeTexture",
+            value: UniformValue::Texture2D(0),
+        });
 
         let antialiasing_samples = 0;
         let antialiasing_pass = render::MsaaPass::new(
             context,
             physical_width,
             physical_height,
-            antialiasing_samples,
+            u32::min(antialiasing_samples, options.capabilities.max_samples),
         )?;
 
         let drawer = Self {
             context: Rc::clone(context),
             settings: Rc::clone(settings),
 
+            logical_width,
+            logical_height,
             physical_width,
             physical_height,
 
@@ -363,6 +425,7 @@ impl Drawer {
             draw_lines_buffer,
             draw_endpoints_buffer,
             draw_texture_buffer,
+            draw_dye_buffer,
 
             view_buffer,
             line_uniforms,
@@ -371,6 +434,7 @@ impl Drawer {
             draw_lines_pass: draw_lines_program,
             draw_endpoints_pass: draw_endpoints_program,
             draw_texture_pass: draw_texture_program,
+            draw_dye_pass: draw_dye_program,
             antialiasing_pass,
         };
 
@@ -395,7 +459,10 @@ impl Drawer {
         }
 
         // Workaround for iOS
-        let color_wheel = settings::color_wheel_from_scheme(&settings.color_scheme);
+        let color_wheel = settings::color_wheel_from_scheme(
+            &settings.color_scheme,
+            &settings.color_interpolation,
+        );
         self.place_lines_pass.set_uniforms(&[
             &Uniform {
                 name: "uLineFadeOutLength",
@@ -437,6 +504,14 @@ impl Drawer {
                 name: "uColorWheel[0]",
                 value: UniformValue::Vec4Array(&color_wheel),
             },
+            &Uniform {
+                name: "uColorCount",
+                value: UniformValue::SignedInt((color_wheel.len() / 4) as i32),
+            },
+            &Uniform {
+                name: "uColorInterpolation",
+                value: UniformValue::SignedInt(settings.color_interpolation.to_uniform()),
+            },
         ]);
     }
 
@@ -449,6 +524,8 @@ impl Drawer {
     ) -> Result<(), render::Problem> {
         let (grid_width, grid_height) = compute_grid_size(logical_width, logical_height);
 
+        self.logical_width = logical_width;
+        self.logical_height = logical_height;
         self.physical_width = physical_width;
         self.physical_height = physical_height;
         self.grid_width = grid_width;
@@ -493,6 +570,53 @@ impl Drawer {
         Ok(())
     }
 
+    // Convert a point in logical pixels, measured from the top-left corner of
+    // the canvas, into world space. This undoes the projection and view scale
+    // used to draw the lines, so the point lands under the cursor.
+    pub fn to_world_space(&self, logical_x: f32, logical_y: f32) -> [f32; 2] {
+        let clip_position = glm::vec4(
+            2.0 * logical_x / self.logical_width as f32 - 1.0,
+            1.0 - 2.0 * logical_y / self.logical_height as f32,
+            0.0,
+            1.0,
+        );
+        let world_position =
+            glm::inverse(&(self.projection_matrix() * self.view_matrix())) * clip_position;
+
+        [world_position.x, world_position.y]
+    }
+
+    // Convert a point in world space into the texture coordinates of the fluid.
+    // The lines sample the velocity without the view scale, so we skip it here
+    // too.
+    pub fn to_fluid_space(&self, world_position: [f32; 2]) -> [f32; 2] {
+        let clip_position =
+            self.projection_matrix() * glm::vec4(world_position[0], world_position[1], 0.0, 1.0);
+
+        [0.5 * clip_position.x + 0.5, 0.5 * clip_position.y + 0.5]
+    }
+
+    // Convert a distance in world space into a fraction of the fluid height.
+    pub fn to_fluid_distance(&self, world_distance: f32) -> f32 {
+        0.5 * world_distance * self.projection_matrix()[(1, 1)]
+    }
+
+    fn projection_matrix(&self) -> glm::TMat4<f32> {
+        new_projection_matrix(
+            self.grid_width as f32,
+            self.grid_height as f32,
+            self.physical_width as f32,
+            self.physical_height as f32,
+        )
+    }
+
+    fn view_matrix(&self) -> glm::TMat4<f32> {
+        glm::scale(
+            &glm::identity(),
+            &glm::vec3(self.settings.view_scale, self.settings.view_scale, 1.0),
+        )
+    }
+
     fn update_line_buffers(&self) -> Result<(), render::Problem> {
         self.place_lines_buffer.update(
             &self.place_lines_pass,
@@ -683,7 +807,12 @@ impl Drawer {
         });
     }
 
-    pub fn place_lines(&self, timestep: f32, texture: &Framebuffer) -> () {
+    pub fn place_lines(
+        &self,
+        timestep: f32,
+        texture: &Framebuffer,
+        obstacle_texture: &Framebuffer,
+    ) -> () {
         unsafe {
             self.context.viewport(
                 0,
@@ -706,6 +835,9 @@ impl Drawer {
             self.context.active_texture(glow::TEXTURE0);
             self.context
                 .bind_texture(glow::TEXTURE_2D, Some(texture.texture));
+            self.context.active_texture(glow::TEXTURE1);
+            self.context
+                .bind_texture(glow::TEXTURE_2D, Some(obstacle_texture.texture));
 
             self.context.bind_transform_feedback(
                 glow::TRANSFORM_FEEDBACK,
@@ -749,6 +881,39 @@ impl Drawer {
         }
     }
 
+    pub fn snapshot(&self) -> DrawerState {
+        DrawerState {
+            line_count: self.line_count,
+            line_state: self.line_state_buffer.read_f32_data(),
+        }
+    }
+
+    // Check that a snapshot has the same number of lines, and the state of
+    // each one, without changing anything. The lines are laid out on a grid
+    // that depends on the size of the screen, so the count changes with it.
+    pub fn check_state(&self, state: &DrawerState) -> Result<(), render::Problem> {
+        if state.line_count != self.line_count {
+            return Err(render::Problem::WrongDataSize {
+                expected: self.line_count as usize,
+                actual: state.line_count as usize,
+            });
+        }
+        if state.line_state.len() != self.line_state_buffer.size {
+            return Err(render::Problem::WrongDataSize {
+                expected: self.line_state_buffer.size,
+                actual: state.line_state.len(),
+            });
+        }
+
+        Ok(())
+    }
+
+    // Load a snapshot of the lines. See `check_state` for what it must match.
+    pub fn restore(&self, state: &DrawerState) -> Result<(), render::Problem> {
+        self.check_state(state)?;
+        self.line_state_buffer.write_f32_data(&state.line_state)
+    }
+
     pub fn draw_lines(&self) -> () {
         unsafe {
             self.context.viewport(
@@ -758,8 +923,7 @@ impl Drawer {
                 self.physical_height as i32,
             );
 
-            self.context.enable(glow::BLEND);
-            self.context.blend_func(glow::SRC_ALPHA, glow::ONE);
+            self.enable_line_blending();
 
             self.draw_lines_pass.use_program();
             self.context
@@ -773,7 +937,7 @@ impl Drawer {
             self.context
                 .draw_arrays_instanced(glow::TRIANGLES, 0, 6, self.line_count as i32);
 
-            self.context.disable(glow::BLEND);
+            self.disable_line_blending();
         }
     }
 
@@ -786,8 +950,7 @@ impl Drawer {
                 self.physical_height as i32,
             );
 
-            self.context.enable(glow::BLEND);
-            self.context.blend_func(glow::SRC_ALPHA, glow::ONE);
+            self.enable_line_blending();
 
             self.draw_endpoints_pass.use_program();
             self.context
@@ -798,6 +961,10 @@ impl Drawer {
             self.context
                 .bind_buffer_base(glow::UNIFORM_BUFFER, 1, Some(self.line_uniforms.id));
 
+            self.draw_endpoints_pass.set_uniform(&Uniform {
+                name: "uBlendMode",
+                value: UniformValue::SignedInt(self.line_blend_mode().to_uniform()),
+            });
             self.draw_endpoints_pass.set_uniform(&Uniform {
                 name: "uOrientation",
                 value: UniformValue::Float(1.0),
@@ -814,10 +981,68 @@ impl Drawer {
             self.context
                 .draw_arrays_instanced(glow::TRIANGLE_FAN, 0, 8, self.line_count as i32);
 
-            self.context.disable(glow::BLEND);
+            self.disable_line_blending();
         }
     }
 
+    // Draw the dye under the lines. Call this after drawing the lines: every
+    // blend mode gives the same result in either order, and drawing the dye
+    // last lets it blend with the finished lines.
+    pub fn draw_dye(&self, texture: &Framebuffer, dye: &Dye) -> () {
+        unsafe {
+            self.context.viewport(
+                0,
+                0,
+                self.physical_width as i32,
+                self.physical_height as i32,
+            );
+
+            self.draw_dye_pass.set_uniform(&Uniform {
+                name: "uOpacity",
+                value: UniformValue::Float(dye.opacity),
+            });
+
+            self.context
+                .bind_buffer_base(glow::UNIFORM_BUFFER, 0, Some(self.view_buffer.id));
+            self.context
+                .bind_vertex_array(Some(self.draw_dye_buffer.id));
+
+            self.context.active_texture(glow::TEXTURE0);
+            self.context
+                .bind_texture(glow::TEXTURE_2D, Some(texture.texture));
+
+            self.context
+                .draw_elements(glow::TRIANGLES, 6, glow::UNSIGNED_SHORT, 0);
+        }
+    }
+
+    // How the lines are combined with whatever is under them. The line shaders
+    // output premultiplied colors.
+    fn line_blend_mode(&self) -> DyeBlendMode {
+        self.settings
+            .dye
+            .as_ref()
+            .map_or(DyeBlendMode::Add, |dye| dye.blend_mode)
+    }
+
+    unsafe fn enable_line_blending(&self) {
+        self.context.enable(glow::BLEND);
+        match self.line_blend_mode() {
+            DyeBlendMode::Add => self.context.blend_func(glow::ONE, glow::ONE),
+            DyeBlendMode::Screen => self
+                .context
+                .blend_func(glow::ONE, glow::ONE_MINUS_SRC_COLOR),
+            DyeBlendMode::Lighten => self.context.blend_equation(glow::MAX),
+        }
+    }
+
+    // Put the blend state back to the GL defaults.
+    unsafe fn disable_line_blending(&self) {
+        self.context.blend_func(glow::ONE, glow::ZERO);
+        self.context.blend_equation(glow::FUNC_ADD);
+        self.context.disable(glow::BLEND);
+    }
+
     #[allow(dead_code)]
     pub fn draw_texture(&self, texture: &Framebuffer) -> () {
         unsafe {
@@ -857,6 +1082,25 @@ impl Drawer {
     }
 }
 
+impl Drop for Drawer {
+    fn drop(&mut self) {
+        unsafe {
+            self.context
+                .delete_transform_feedback(self.transform_feedback_buffer);
+        }
+        render::track_deleted(1);
+    }
+}
+
+// Name the values of `DyeBlendMode::to_uniform` for `endpoint.vert`.
+fn blend_mode_defines() -> [Define<'static>; 3] {
+    [
+        ("ADD", DyeBlendMode::Add.to_uniform().to_string()),
+        ("SCREEN", DyeBlendMode::Screen.to_uniform().to_string()),
+        ("LIGHTEN", DyeBlendMode::Lighten.to_uniform().to_string()),
+    ]
+}
+
 fn compute_grid_size(logical_width: u32, logical_height: u32) -> (u32, u32) {
     if logical_width > logical_height {
         (u32::max(1280, logical_width), u32::max(800, logical_height))
@@ -1,26 +1,59 @@
-use crate::{data, render, settings};
+use crate::{data, obstacle, render, settings};
+use obstacle::ObstacleMask;
 use render::{
-    Buffer, Context, DoubleFramebuffer, Framebuffer, TextureOptions, Uniform, UniformValue,
-    VertexArrayObject,
+    Buffer, Context, Define, DoubleFramebuffer, FieldFormat, FieldResampler, Framebuffer, Uniform,
+    UniformValue, VertexArrayObject,
 };
-use settings::Settings;
+use settings::{AdvectionScheme, Boundary, PressureSolver, Settings};
 
 use bytemuck::{Pod, Zeroable};
 use glow::HasContext;
 use std::cell::Ref;
 use std::rc::Rc;
 
-static FLUID_VERT_SHADER: &'static str =
-    include_str!(concat!(env!("OUT_DIR"), "/shaders/fluid.vert"));
-static ADVECTION_FRAG_SHADER: &'static str =
-    include_str!(concat!(env!("OUT_DIR"), "/shaders/advection.frag"));
-static DIVERGENCE_FRAG_SHADER: &'static str =
-    include_str!(concat!(env!("OUT_DIR"), "/shaders/divergence.frag"));
-static SOLVE_PRESSURE_FRAG_SHADER: &'static str =
-    include_str!(concat!(env!("OUT_DIR"), "/shaders/solve_pressure.frag"));
+static FLUID_VERT_SHADER: &'static str = include_str!("../shaders/fluid.vert");
+static ADVECTION_FRAG_SHADER: &'static str = include_str!("../shaders/advection.frag");
+static MACCORMACK_FRAG_SHADER: &'static str = include_str!("../shaders/maccormack.frag");
+static BFECC_FRAG_SHADER: &'static str = include_str!("../shaders/bfecc.frag");
+static DIVERGENCE_FRAG_SHADER: &'static str = include_str!("../shaders/divergence.frag");
+static SOLVE_PRESSURE_FRAG_SHADER: &'static str = include_str!("../shaders/solve_pressure.frag");
 static SUBTRACT_GRADIENT_FRAG_SHADER: &'static str =
-    include_str!(concat!(env!("OUT_DIR"), "/shaders/subtract_gradient.frag"));
+    include_str!("../shaders/subtract_gradient.frag");
+static CURL_FRAG_SHADER: &'static str = include_str!("../shaders/curl.frag");
+static VORTICITY_FRAG_SHADER: &'static str = include_str!("../shaders/vorticity.frag");
+static RELAX_PRESSURE_FRAG_SHADER: &'static str = include_str!("../shaders/relax_pressure.frag");
+static PRESSURE_RESIDUAL_FRAG_SHADER: &'static str =
+    include_str!("../shaders/pressure_residual.frag");
+static PROLONGATE_FRAG_SHADER: &'static str = include_str!("../shaders/prolongate.frag");
+static SPLAT_FRAG_SHADER: &'static str = include_str!("../shaders/splat.frag");
+static ADVECT_QUANTITY_FRAG_SHADER: &'static str = include_str!("../shaders/advect_quantity.frag");
+static SPLAT_QUANTITY_FRAG_SHADER: &'static str = include_str!("../shaders/splat_quantity.frag");
+static BUOYANCY_FRAG_SHADER: &'static str = include_str!("../shaders/buoyancy.frag");
 
+// The texture unit the obstacle mask stays bound to during a fluid step.
+const OBSTACLE_TEXTURE_UNIT: u32 = 2;
+// The texture unit for the round trip of the MacCormack and BFECC schemes,
+// past the obstacle mask.
+const BACKWARD_TEXTURE_UNIT: u32 = 3;
+
+// Stop coarsening the multigrid hierarchy once a level would be smaller than
+// this, in either direction.
+const MIN_MULTIGRID_SIZE: u32 = 8;
+// Iterations used to solve the coarsest multigrid level. It’s tiny, so this is
+// cheap.
+pub const COARSEST_MULTIGRID_ITERATIONS: u32 = 20;
+// How many Jacobi iterations to run between convergence checks when
+// `pressure_tolerance` is set. Each check waits on the GPU, so don’t check too
+// often.
+pub const PRESSURE_CHECK_INTERVAL: u32 = 10;
+// The weight of the weighted Jacobi smoother. 4/5 damps high frequencies best
+// for the 5-point Laplacian.
+pub const MULTIGRID_SMOOTHING_WEIGHT: f32 = 0.8;
+// The most splats queued between two steps. Each one is a full-screen pass,
+// so the oldest are dropped past this.
+const MAX_QUEUED_SPLATS: usize = 32;
+
+// Must match `shaders/common/fluid_uniforms.glsl`
 #[repr(C)]
 #[derive(Clone, Copy, Pod, Zeroable)]
 struct Uniforms {
@@ -29,10 +62,56 @@ struct Uniforms {
     half_epsilon: f32,
     dissipation: f32,
     texel_size: [f32; 2],
-    pad1: f32,
+    boundary: i32,
     pad2: f32,
 }
 
+// A Gaussian splat of velocity. The position is in texture coordinates and the
+// radius is a fraction of the fluid height.
+#[derive(Clone, Copy, Debug)]
+pub struct Splat {
+    pub position: [f32; 2],
+    pub force: [f32; 2],
+    pub vortex_strength: f32,
+    pub radius: f32,
+}
+
+// The fields that carry over from one step to the next, read back from the
+// GPU. Each one is packed like `Framebuffer::read_data`, and the whole grid is
+// `width` by `height`.
+#[derive(Clone, Debug)]
+pub struct FluidState {
+    pub width: u32,
+    pub height: u32,
+    pub velocity: Vec<f32>,
+    pub pressure: Vec<f32>,
+    pub dye: Vec<f32>,
+    pub temperature: Vec<f32>,
+}
+
+// A coarser copy of the pressure grid, used by the multigrid solver. Each level
+// is half the size of the one above it.
+struct MultigridLevel {
+    texel_size: [f32; 2],
+    grid_size: f32,
+
+    // The residual of the finer level, restricted to this grid
+    divergence_texture: Framebuffer,
+    // The correction to the pressure of the finer level
+    pressure_textures: DoubleFramebuffer,
+    residual_texture: Framebuffer,
+}
+
+// The textures for one level of the pressure solve, with the full-size grid
+// at level 0.
+struct PressureGrid<'a> {
+    texel_size: [f32; 2],
+    grid_size: f32,
+    divergence_texture: &'a Framebuffer,
+    pressure_textures: &'a DoubleFramebuffer,
+    residual_texture: &'a Framebuffer,
+}
+
 pub struct Fluid {
     context: Context,
     settings: Rc<Settings>,
@@ -41,19 +120,48 @@ pub struct Fluid {
     pub height: u32,
     texel_size: [f32; 2],
     grid_size: f32,
+    field_format: FieldFormat,
 
     uniform_buffer: Buffer,
     vertex_buffer: VertexArrayObject,
 
     velocity_textures: DoubleFramebuffer,
+    // Intermediate steps of the MacCormack and BFECC advection schemes
+    forward_advection_texture: Framebuffer,
+    backward_advection_texture: Framebuffer,
     divergence_texture: Framebuffer,
+    curl_texture: Framebuffer,
     pressure_textures: DoubleFramebuffer,
+    residual_texture: Framebuffer,
+    multigrid_levels: Vec<MultigridLevel>,
+    obstacle_texture: Framebuffer,
+    // An image of extra obstacles, on top of those in the settings
+    obstacle_mask: Option<ObstacleMask>,
+    // Premultiplied RGBA dye, carried along by the velocity
+    dye_textures: DoubleFramebuffer,
+    // Temperature relative to the surroundings, carried along by the velocity
+    temperature_textures: DoubleFramebuffer,
+
+    // Splats waiting to be added on the next step
+    splats: Vec<Splat>,
 
     advection_pass: render::Program,
+    maccormack_pass: render::Program,
+    bfecc_pass: render::Program,
     diffusion_pass: render::Program,
     divergence_pass: render::Program,
+    curl_pass: render::Program,
+    vorticity_pass: render::Program,
     pressure_pass: render::Program,
+    relax_pressure_pass: render::Program,
+    pressure_residual_pass: render::Program,
+    prolongate_pass: render::Program,
     subtract_gradient_pass: render::Program,
+    splat_pass: render::Program,
+    advect_quantity_pass: render::Program,
+    splat_quantity_pass: render::Program,
+    buoyancy_pass: render::Program,
+    resampler: FieldResampler,
 }
 
 impl Fluid {
@@ -61,6 +169,7 @@ impl Fluid {
         context: &Context,
         ratio: f32,
         settings: &Rc<Settings>,
+        field_format: FieldFormat,
     ) -> Result<Self, render::Problem> {
         let grid_size: f32 = 1.0;
         let (width, height, texel_size) = compute_fluid_size(settings.fluid_size as f32, ratio);
@@ -68,42 +177,59 @@ impl Fluid {
         // Framebuffers
         let initial_velocity_data = vec![0.0; (2 * width * height) as usize];
 
-        let velocity_textures = render::DoubleFramebuffer::new(
-            &context,
-            width,
-            height,
-            TextureOptions {
-                mag_filter: glow::LINEAR,
-                min_filter: glow::LINEAR,
-                format: glow::RG32F,
-                ..Default::default()
-            },
-        )?
-        .with_f32_data(&initial_velocity_data)?;
+        let velocity_textures =
+            render::DoubleFramebuffer::new(&context, width, height, field_format.field_options())?
+                .with_f32_data(&initial_velocity_data)?;
 
-        let divergence_texture = render::Framebuffer::new(
+        let forward_advection_texture =
+            render::Framebuffer::new(&context, width, height, field_format.field_options())?
+                .with_f32_data(&initial_velocity_data)?;
+
+        let backward_advection_texture =
+            render::Framebuffer::new(&context, width, height, field_format.field_options())?
+                .with_f32_data(&initial_velocity_data)?;
+
+        let divergence_texture =
+            render::Framebuffer::new(&context, width, height, field_format.field_options())?
+                .with_f32_data(&vec![0.0; (2 * width * height) as usize])?;
+
+        let curl_texture =
+            render::Framebuffer::new(&context, width, height, field_format.field_options())?
+                .with_f32_data(&vec![0.0; (2 * width * height) as usize])?;
+
+        let pressure_textures =
+            render::DoubleFramebuffer::new(&context, width, height, field_format.field_options())?
+                .with_f32_data(&vec![0.0; (2 * width * height) as usize])?;
+
+        let residual_texture =
+            render::Framebuffer::new(&context, width, height, field_format.field_options())?
+                .with_f32_data(&vec![0.0; (2 * width * height) as usize])?;
+
+        let multigrid_levels =
+            create_multigrid_levels(&context, width, height, grid_size, field_format)?;
+
+        let obstacle_texture =
+            render::Framebuffer::new(&context, width, height, field_format.sampled_options())?
+                .with_f32_data(bytemuck::cast_slice(&obstacle::rasterize(
+                    &settings.obstacles,
+                    None,
+                    width,
+                    height,
+                )))?;
+
+        let dye_textures = render::DoubleFramebuffer::new(
             &context,
             width,
             height,
-            TextureOptions {
-                mag_filter: glow::LINEAR,
-                min_filter: glow::LINEAR,
-                format: glow::RG32F,
-                ..Default::default()
-            },
+            field_format.quantity_options(4),
         )?
-        .with_f32_data(&vec![0.0; (2 * width * height) as usize])?;
+        .with_f32_data(&vec![0.0; (4 * width * height) as usize])?;
 
-        let pressure_textures = render::DoubleFramebuffer::new(
+        let temperature_textures = render::DoubleFramebuffer::new(
             &context,
             width,
             height,
-            TextureOptions {
-                mag_filter: glow::LINEAR,
-                min_filter: glow::LINEAR,
-                format: glow::RG32F,
-                ..Default::default()
-            },
+            field_format.quantity_options(2),
         )?
         .with_f32_data(&vec![0.0; (2 * width * height) as usize])?;
 
@@ -121,15 +247,103 @@ impl Fluid {
             glow::STATIC_DRAW,
         )?;
 
-        let advection_program =
-            render::Program::new(&context, (FLUID_VERT_SHADER, ADVECTION_FRAG_SHADER))?;
-        let divergence_program =
-            render::Program::new(&context, (FLUID_VERT_SHADER, DIVERGENCE_FRAG_SHADER))?;
-        let pressure_program =
-            render::Program::new(&context, (FLUID_VERT_SHADER, SOLVE_PRESSURE_FRAG_SHADER))?;
-        let diffusion_program = pressure_program.clone();
-        let subtract_gradient_program =
-            render::Program::new(&context, (FLUID_VERT_SHADER, SUBTRACT_GRADIENT_FRAG_SHADER))?;
+        let defines = boundary_defines();
+        let advection_program = render::Program::new_for_fields(
+            &context,
+            (FLUID_VERT_SHADER, ADVECTION_FRAG_SHADER),
+            &defines,
+            field_format,
+        )?;
+        let maccormack_program = render::Program::new_for_fields(
+            &context,
+            (FLUID_VERT_SHADER, MACCORMACK_FRAG_SHADER),
+            &defines,
+            field_format,
+        )?;
+        let bfecc_program = render::Program::new_for_fields(
+            &context,
+            (FLUID_VERT_SHADER, BFECC_FRAG_SHADER),
+            &defines,
+            field_format,
+        )?;
+        let divergence_program = render::Program::new_for_fields(
+            &context,
+            (FLUID_VERT_SHADER, DIVERGENCE_FRAG_SHADER),
+            &defines,
+            field_format,
+        )?;
+        let curl_program = render::Program::new_for_fields(
+            &context,
+            (FLUID_VERT_SHADER, CURL_FRAG_SHADER),
+            &defines,
+            field_format,
+        )?;
+        let vorticity_program = render::Program::new_for_fields(
+            &context,
+            (FLUID_VERT_SHADER, VORTICITY_FRAG_SHADER),
+            &defines,
+            field_format,
+        )?;
+        let pressure_program = render::Program::new_for_fields(
+            &context,
+            (FLUID_VERT_SHADER, SOLVE_PRESSURE_FRAG_SHADER),
+            &defines,
+            field_format,
+        )?;
+        let diffusion_program = render::Program::new_for_fields(
+            &context,
+            (FLUID_VERT_SHADER, SOLVE_PRESSURE_FRAG_SHADER),
+            &defines,
+            field_format,
+        )?;
+        let relax_pressure_program = render::Program::new_for_fields(
+            &context,
+            (FLUID_VERT_SHADER, RELAX_PRESSURE_FRAG_SHADER),
+            &defines,
+            field_format,
+        )?;
+        let pressure_residual_program = render::Program::new_for_fields(
+            &context,
+            (FLUID_VERT_SHADER, PRESSURE_RESIDUAL_FRAG_SHADER),
+            &defines,
+            field_format,
+        )?;
+        let prolongate_program = render::Program::new_for_fields(
+            &context,
+            (FLUID_VERT_SHADER, PROLONGATE_FRAG_SHADER),
+            &defines,
+            field_format,
+        )?;
+        let subtract_gradient_program = render::Program::new_for_fields(
+            &context,
+            (FLUID_VERT_SHADER, SUBTRACT_GRADIENT_FRAG_SHADER),
+            &defines,
+            field_format,
+        )?;
+        let splat_program = render::Program::new_for_fields(
+            &context,
+            (FLUID_VERT_SHADER, SPLAT_FRAG_SHADER),
+            &defines,
+            field_format,
+        )?;
+        let advect_quantity_program = render::Program::new_for_fields(
+            &context,
+            (FLUID_VERT_SHADER, ADVECT_QUANTITY_FRAG_SHADER),
+            &defines,
+            field_format,
+        )?;
+        let splat_quantity_program = render::Program::new_for_fields(
+            &context,
+            (FLUID_VERT_SHADER, SPLAT_QUANTITY_FRAG_SHADER),
+            &defines,
+            field_format,
+        )?;
+        let buoyancy_program = render::Program::new_for_fields(
+            &context,
+            (FLUID_VERT_SHADER, BUOYANCY_FRAG_SHADER),
+            &defines,
+            field_format,
+        )?;
 
         let uniforms = Uniforms {
             timestep: 0.0,
@@ -137,7 +351,7 @@ impl Fluid {
             half_epsilon: 0.5 * grid_size,
             dissipation: settings.velocity_dissipation,
             texel_size,
-            pad1: 0.0,
+            boundary: settings.boundary.to_uniform(),
             pad2: 0.0,
         };
 
@@ -149,10 +363,21 @@ impl Fluid {
         )?;
 
         advection_program.set_uniform_block("FluidUniforms", 0);
+        maccormack_program.set_uniform_block("FluidUniforms", 0);
+        bfecc_program.set_uniform_block("FluidUniforms", 0);
         diffusion_program.set_uniform_block("FluidUniforms", 0);
         divergence_program.set_uniform_block("FluidUniforms", 0);
+        curl_program.set_uniform_block("FluidUniforms", 0);
+        vorticity_program.set_uniform_block("FluidUniforms", 0);
         pressure_program.set_uniform_block("FluidUniforms", 0);
+        relax_pressure_program.set_uniform_block("FluidUniforms", 0);
+        pressure_residual_program.set_uniform_block("FluidUniforms", 0);
+        prolongate_program.set_uniform_block("FluidUniforms", 0);
         subtract_gradient_program.set_uniform_block("FluidUniforms", 0);
+        splat_program.set_uniform_block("FluidUniforms", 0);
+        advect_quantity_program.set_uniform_block("FluidUniforms", 0);
+        splat_quantity_program.set_uniform_block("FluidUniforms", 0);
+        buoyancy_program.set_uniform_block("FluidUniforms", 0);
 
         // TODO can I add this to the uniform buffer? Is that even worth it?
         advection_program.set_uniforms(&[
@@ -164,6 +389,38 @@ impl Fluid {
                 name: "velocityTexture",
                 value: UniformValue::Texture2D(1),
             },
+            &Uniform {
+                name: "uDirection",
+                value: UniformValue::Float(1.0),
+            },
+            &Uniform {
+                name: "uLimit",
+                value: UniformValue::UnsignedInt(0),
+            },
+        ]);
+        maccormack_program.set_uniforms(&[
+            &Uniform {
+                name: "velocityTexture",
+                value: UniformValue::Texture2D(0),
+            },
+            &Uniform {
+                name: "forwardTexture",
+                value: UniformValue::Texture2D(1),
+            },
+            &Uniform {
+                name: "backwardTexture",
+                value: UniformValue::Texture2D(BACKWARD_TEXTURE_UNIT),
+            },
+        ]);
+        bfecc_program.set_uniforms(&[
+            &Uniform {
+                name: "velocityTexture",
+                value: UniformValue::Texture2D(0),
+            },
+            &Uniform {
+                name: "backwardTexture",
+                value: UniformValue::Texture2D(BACKWARD_TEXTURE_UNIT),
+            },
         ]);
         diffusion_program.set_uniforms(&[
             &Uniform {
@@ -174,11 +431,29 @@ impl Fluid {
                 name: "pressureTexture",
                 value: UniformValue::Texture2D(1),
             },
+            &Uniform {
+                name: "zeroPastOpenEdges",
+                value: UniformValue::UnsignedInt(0),
+            },
         ]);
         divergence_program.set_uniform(&Uniform {
             name: "velocityTexture",
             value: UniformValue::Texture2D(0),
         });
+        curl_program.set_uniform(&Uniform {
+            name: "velocityTexture",
+            value: UniformValue::Texture2D(0),
+        });
+        vorticity_program.set_uniforms(&[
+            &Uniform {
+                name: "velocityTexture",
+                value: UniformValue::Texture2D(0),
+            },
+            &Uniform {
+                name: "curlTexture",
+                value: UniformValue::Texture2D(1),
+            },
+        ]);
         pressure_program.set_uniforms(&[
             &Uniform {
                 name: "divergenceTexture",
@@ -188,6 +463,48 @@ impl Fluid {
                 name: "pressureTexture",
                 value: UniformValue::Texture2D(1),
             },
+            &Uniform {
+                name: "zeroPastOpenEdges",
+                value: UniformValue::UnsignedInt(1),
+            },
+        ]);
+        relax_pressure_program.set_uniforms(&[
+            &Uniform {
+                name: "divergenceTexture",
+                value: UniformValue::Texture2D(0),
+            },
+            &Uniform {
+                name: "pressureTexture",
+                value: UniformValue::Texture2D(1),
+            },
+            &Uniform {
+                name: "rBeta",
+                value: UniformValue::Float(0.25),
+            },
+            &Uniform {
+                name: "omega",
+                value: UniformValue::Float(MULTIGRID_SMOOTHING_WEIGHT),
+            },
+        ]);
+        pressure_residual_program.set_uniforms(&[
+            &Uniform {
+                name: "divergenceTexture",
+                value: UniformValue::Texture2D(0),
+            },
+            &Uniform {
+                name: "pressureTexture",
+                value: UniformValue::Texture2D(1),
+            },
+        ]);
+        prolongate_program.set_uniforms(&[
+            &Uniform {
+                name: "pressureTexture",
+                value: UniformValue::Texture2D(0),
+            },
+            &Uniform {
+                name: "correctionTexture",
+                value: UniformValue::Texture2D(1),
+            },
         ]);
         subtract_gradient_program.set_uniforms(&[
             &Uniform {
@@ -199,6 +516,51 @@ impl Fluid {
                 value: UniformValue::Texture2D(1),
             },
         ]);
+        splat_program.set_uniform(&Uniform {
+            name: "velocityTexture",
+            value: UniformValue::Texture2D(0),
+        });
+        advect_quantity_program.set_uniforms(&[
+            &Uniform {
+                name: "velocityTexture",
+                value: UniformValue::Texture2D(0),
+            },
+            &Uniform {
+                name: "quantityTexture",
+                value: UniformValue::Texture2D(1),
+            },
+        ]);
+        splat_quantity_program.set_uniform(&Uniform {
+            name: "quantityTexture",
+            value: UniformValue::Texture2D(0),
+        });
+        buoyancy_program.set_uniforms(&[
+            &Uniform {
+                name: "velocityTexture",
+                value: UniformValue::Texture2D(0),
+            },
+            &Uniform {
+                name: "temperatureTexture",
+                value: UniformValue::Texture2D(1),
+            },
+        ]);
+
+        for program in [
+            &advection_program,
+            &maccormack_program,
+            &diffusion_program,
+            &divergence_program,
+            &pressure_program,
+            &relax_pressure_program,
+            &pressure_residual_program,
+            &subtract_gradient_program,
+            &advect_quantity_program,
+        ] {
+            program.set_uniform(&Uniform {
+                name: "obstacleTexture",
+                value: UniformValue::Texture2D(OBSTACLE_TEXTURE_UNIT),
+            });
+        }
 
         let vertex_buffer = VertexArrayObject::new(
             &context,
@@ -215,7 +577,7 @@ impl Fluid {
             Some(&plane_indices),
         )?;
 
-        Ok(Self {
+        let mut fluid = Self {
             context: Rc::clone(context),
             settings: Rc::clone(settings),
 
@@ -223,23 +585,50 @@ impl Fluid {
             height,
             texel_size,
             grid_size,
+            field_format,
 
             uniform_buffer,
             vertex_buffer,
 
             velocity_textures,
+            forward_advection_texture,
+            backward_advection_texture,
             divergence_texture,
+            curl_texture,
             pressure_textures,
+            residual_texture,
+            multigrid_levels,
+            obstacle_texture,
+            obstacle_mask: None,
+            dye_textures,
+            temperature_textures,
+
+            splats: Vec::new(),
 
             advection_pass: advection_program,
-            diffusion_pass: pressure_program.clone(),
+            maccormack_pass: maccormack_program,
+            bfecc_pass: bfecc_program,
+            diffusion_pass: diffusion_program,
             divergence_pass: divergence_program,
+            curl_pass: curl_program,
+            vorticity_pass: vorticity_program,
             pressure_pass: pressure_program,
+            relax_pressure_pass: relax_pressure_program,
+            pressure_residual_pass: pressure_residual_program,
+            prolongate_pass: prolongate_program,
             subtract_gradient_pass: subtract_gradient_program,
-        })
+            splat_pass: splat_program,
+            advect_quantity_pass: advect_quantity_program,
+            splat_quantity_pass: splat_quantity_program,
+            buoyancy_pass: buoyancy_program,
+            resampler: FieldResampler::new(context, field_format)?,
+        };
+        fluid.set_texture_wrap();
+
+        Ok(fluid)
     }
 
-    pub fn update(&mut self, settings: &Rc<Settings>) -> () {
+    pub fn update(&mut self, settings: &Rc<Settings>) -> Result<(), render::Problem> {
         self.settings = Rc::clone(settings); // Fix
 
         let uniforms = Uniforms {
@@ -248,7 +637,7 @@ impl Fluid {
             half_epsilon: 0.5 * self.grid_size,
             dissipation: settings.velocity_dissipation,
             texel_size: self.texel_size,
-            pad1: 0.0,
+            boundary: settings.boundary.to_uniform(),
             pad2: 0.0,
         };
 
@@ -262,6 +651,19 @@ impl Fluid {
             );
             self.context.bind_buffer(glow::UNIFORM_BUFFER, None);
         }
+
+        self.set_texture_wrap();
+        self.upload_obstacles()?;
+
+        // Start afresh if the dye or temperature are turned back on later
+        if settings.dye.is_none() {
+            self.dye_textures.zero_out()?;
+        }
+        if settings.temperature.is_none() {
+            self.temperature_textures.zero_out()?;
+        }
+
+        Ok(())
     }
 
     pub fn resize(&mut self, ratio: f32) -> Result<(), render::Problem> {
@@ -271,17 +673,7 @@ impl Fluid {
         self.height = height;
         self.texel_size = texel_size;
 
-        // Update texel size
-        unsafe {
-            self.context
-                .bind_buffer(glow::UNIFORM_BUFFER, Some(self.uniform_buffer.id));
-            self.context.buffer_sub_data_u8_slice(
-                glow::UNIFORM_BUFFER,
-                4 * 4,
-                &bytemuck::bytes_of(&texel_size),
-            );
-            self.context.bind_buffer(glow::UNIFORM_BUFFER, None);
-        }
+        self.set_texel_size(texel_size);
 
         // Create new textures and copy the old contents over
         let velocity_textures = render::DoubleFramebuffer::new(
@@ -291,10 +683,25 @@ impl Fluid {
             self.velocity_textures.current().options,
         )?
         .with_data(None::<&[f32]>)?;
-        self.velocity_textures
-            .blit_to(&self.context, &velocity_textures);
+        self.resampler
+            .resample_double(&self.velocity_textures, &velocity_textures);
         self.velocity_textures = velocity_textures;
 
+        self.forward_advection_texture = render::Framebuffer::new(
+            &self.context,
+            width,
+            height,
+            self.forward_advection_texture.options,
+        )?
+        .with_data(None::<&[f32]>)?;
+        self.backward_advection_texture = render::Framebuffer::new(
+            &self.context,
+            width,
+            height,
+            self.backward_advection_texture.options,
+        )?
+        .with_data(None::<&[f32]>)?;
+
         let divergence_texture = render::Framebuffer::new(
             &self.context,
             width,
@@ -302,10 +709,15 @@ impl Fluid {
             self.divergence_texture.options,
         )?
         .with_data(None::<&[f32]>)?;
-        self.divergence_texture
-            .blit_to(&self.context, &divergence_texture);
+        self.resampler
+            .resample(&self.divergence_texture, &divergence_texture);
         self.divergence_texture = divergence_texture;
 
+        let curl_texture =
+            render::Framebuffer::new(&self.context, width, height, self.curl_texture.options)?
+                .with_data(None::<&[f32]>)?;
+        self.curl_texture = curl_texture;
+
         let pressure_textures = render::DoubleFramebuffer::new(
             &self.context,
             width,
@@ -313,13 +725,111 @@ impl Fluid {
             self.pressure_textures.current().options,
         )?
         .with_data(None::<&[f32]>)?;
-        self.pressure_textures
-            .blit_to(&self.context, &pressure_textures);
+        self.resampler
+            .resample_double(&self.pressure_textures, &pressure_textures);
         self.pressure_textures = pressure_textures;
 
+        let dye_textures = render::DoubleFramebuffer::new(
+            &self.context,
+            width,
+            height,
+            self.dye_textures.current().options,
+        )?
+        .with_data(None::<&[f32]>)?;
+        self.resampler
+            .resample_double(&self.dye_textures, &dye_textures);
+        self.dye_textures = dye_textures;
+
+        let temperature_textures = render::DoubleFramebuffer::new(
+            &self.context,
+            width,
+            height,
+            self.temperature_textures.current().options,
+        )?
+        .with_data(None::<&[f32]>)?;
+        self.resampler
+            .resample_double(&self.temperature_textures, &temperature_textures);
+        self.temperature_textures = temperature_textures;
+
+        self.residual_texture =
+            render::Framebuffer::new(&self.context, width, height, self.residual_texture.options)?
+                .with_data(None::<&[f32]>)?;
+        self.multigrid_levels = create_multigrid_levels(
+            &self.context,
+            width,
+            height,
+            self.grid_size,
+            self.field_format,
+        )?;
+
+        self.obstacle_texture =
+            render::Framebuffer::new(&self.context, width, height, self.obstacle_texture.options)?;
+        self.set_texture_wrap();
+        self.upload_obstacles()?;
+
         Ok(())
     }
 
+    // Replace the image of extra obstacles. Pass `None` to go back to just the
+    // obstacles in the settings.
+    pub fn set_obstacle_mask(&mut self, mask: Option<ObstacleMask>) -> Result<(), render::Problem> {
+        self.obstacle_mask = mask;
+        self.upload_obstacles()
+    }
+
+    // Periodic boundaries wrap every lookup around to the opposite edge, so
+    // let the samplers do the work.
+    fn set_texture_wrap(&mut self) -> () {
+        let wrap = match self.settings.boundary {
+            Boundary::Periodic => glow::REPEAT,
+            _ => glow::CLAMP_TO_EDGE,
+        };
+
+        self.velocity_textures.set_wrap(wrap);
+        self.forward_advection_texture.set_wrap(wrap);
+        self.backward_advection_texture.set_wrap(wrap);
+        self.divergence_texture.set_wrap(wrap);
+        self.curl_texture.set_wrap(wrap);
+        self.pressure_textures.set_wrap(wrap);
+        self.residual_texture.set_wrap(wrap);
+        self.obstacle_texture.set_wrap(wrap);
+        self.dye_textures.set_wrap(wrap);
+        self.temperature_textures.set_wrap(wrap);
+        for level in self.multigrid_levels.iter_mut() {
+            level.divergence_texture.set_wrap(wrap);
+            level.pressure_textures.set_wrap(wrap);
+            level.residual_texture.set_wrap(wrap);
+        }
+    }
+
+    // Redraw the obstacles into the mask texture.
+    fn upload_obstacles(&self) -> Result<(), render::Problem> {
+        let mask = obstacle::rasterize(
+            &self.settings.obstacles,
+            self.obstacle_mask.as_ref(),
+            self.width,
+            self.height,
+        );
+
+        self.obstacle_texture
+            .write_data(Some(bytemuck::cast_slice::<_, f32>(&mask)))
+    }
+
+    // Update `uTexelSize` in the uniform buffer. The multigrid solver uses
+    // this to draw to the coarser levels.
+    fn set_texel_size(&self, texel_size: [f32; 2]) -> () {
+        unsafe {
+            self.context
+                .bind_buffer(glow::UNIFORM_BUFFER, Some(self.uniform_buffer.id));
+            self.context.buffer_sub_data_u8_slice(
+                glow::UNIFORM_BUFFER,
+                4 * 4,
+                &bytemuck::bytes_of(&texel_size),
+            );
+            self.context.bind_buffer(glow::UNIFORM_BUFFER, None);
+        }
+    }
+
     // Setup vertex and uniform buffers.
     pub fn prepare_pass(&self, timestep: f32) {
         unsafe {
@@ -332,29 +842,252 @@ impl Fluid {
                 &bytemuck::bytes_of(&timestep),
             );
             self.context.bind_buffer(glow::UNIFORM_BUFFER, None);
+        }
+
+        self.bind_shared_state();
+    }
 
+    // Bind the geometry, uniforms, and obstacles used by every fluid pass.
+    fn bind_shared_state(&self) -> () {
+        unsafe {
             self.context.bind_vertex_array(Some(self.vertex_buffer.id));
 
             self.context
                 .bind_buffer_base(glow::UNIFORM_BUFFER, 0, Some(self.uniform_buffer.id));
+
+            self.context
+                .active_texture(glow::TEXTURE0 + OBSTACLE_TEXTURE_UNIT);
+            self.context
+                .bind_texture(glow::TEXTURE_2D, Some(self.obstacle_texture.texture));
         }
     }
 
-    pub fn advect(&self) -> () {
-        self.velocity_textures
-            .draw_to(&self.context, |velocity_texture| unsafe {
-                self.advection_pass.use_program();
-
-                self.context.active_texture(glow::TEXTURE0);
-                self.context
-                    .bind_texture(glow::TEXTURE_2D, Some(velocity_texture.texture));
-                self.context.active_texture(glow::TEXTURE1);
-                self.context
-                    .bind_texture(glow::TEXTURE_2D, Some(velocity_texture.texture));
+    pub fn add_splat(&mut self, splat: Splat) -> () {
+        if self.splats.len() >= MAX_QUEUED_SPLATS {
+            self.splats.remove(0);
+        }
+        self.splats.push(splat);
+    }
 
-                self.context
-                    .draw_elements(glow::TRIANGLES, 6, glow::UNSIGNED_SHORT, 0);
-            });
+    // Blend the queued splats into the velocity field.
+    pub fn apply_splats(&mut self) -> () {
+        for splat in std::mem::take(&mut self.splats) {
+            self.splat_pass.set_uniforms(&[
+                &Uniform {
+                    name: "uPosition",
+                    value: UniformValue::Vec2(&splat.position),
+                },
+                &Uniform {
+                    name: "uForce",
+                    value: UniformValue::Vec2(&splat.force),
+                },
+                &Uniform {
+                    name: "uRadius",
+                    value: UniformValue::Float(splat.radius),
+                },
+                &Uniform {
+                    name: "uVortexStrength",
+                    value: UniformValue::Float(splat.vortex_strength),
+                },
+            ]);
+
+            self.velocity_textures
+                .draw_to(&self.context, |velocity_texture| unsafe {
+                    self.context.active_texture(glow::TEXTURE0);
+                    self.context
+                        .bind_texture(glow::TEXTURE_2D, Some(velocity_texture.texture));
+
+                    self.context
+                        .draw_elements(glow::TRIANGLES, 6, glow::UNSIGNED_SHORT, 0);
+                });
+
+            // Dye and heat follow the strength of the splat
+            let strength = (splat.force[0].powi(2) + splat.force[1].powi(2)).sqrt()
+                + splat.vortex_strength.abs();
+
+            if let Some(dye) = &self.settings.dye {
+                self.splat_quantity(
+                    &self.dye_textures,
+                    &splat,
+                    dye.splat_amount * strength,
+                    [dye.color.r, dye.color.g, dye.color.b, 1.0],
+                );
+            }
+
+            if let Some(temperature) = &self.settings.temperature {
+                self.splat_quantity(
+                    &self.temperature_textures,
+                    &splat,
+                    temperature.splat_amount * strength,
+                    [1.0, 0.0, 0.0, 0.0],
+                );
+            }
+        }
+    }
+
+    // Add a splat of `value`, scaled by `amount`, to a quantity like the dye.
+    fn splat_quantity(
+        &self,
+        textures: &DoubleFramebuffer,
+        splat: &Splat,
+        amount: f32,
+        value: [f32; 4],
+    ) -> () {
+        self.splat_quantity_pass.set_uniforms(&[
+            &Uniform {
+                name: "uPosition",
+                value: UniformValue::Vec2(&splat.position),
+            },
+            &Uniform {
+                name: "uRadius",
+                value: UniformValue::Float(splat.radius),
+            },
+            &Uniform {
+                name: "uAmount",
+                value: UniformValue::Float(amount),
+            },
+            &Uniform {
+                name: "uValue",
+                value: UniformValue::Vec4Array(&value),
+            },
+        ]);
+
+        textures.draw_to(&self.context, |quantity_texture| unsafe {
+            self.context.active_texture(glow::TEXTURE0);
+            self.context
+                .bind_texture(glow::TEXTURE_2D, Some(quantity_texture.texture));
+
+            self.context
+                .draw_elements(glow::TRIANGLES, 6, glow::UNSIGNED_SHORT, 0);
+        });
+    }
+
+    pub fn advect(&self) -> () {
+        match self.settings.advection_scheme {
+            AdvectionScheme::SemiLagrangian => {
+                self.velocity_textures
+                    .draw_to(&self.context, |velocity_texture| {
+                        self.advect_with(velocity_texture, velocity_texture, 1.0, false);
+                    });
+            }
+
+            AdvectionScheme::MacCormack => {
+                self.trace_round_trip();
+
+                self.velocity_textures
+                    .draw_to(&self.context, |velocity_texture| unsafe {
+                        self.maccormack_pass.use_program();
+
+                        self.context.active_texture(glow::TEXTURE0);
+                        self.context
+                            .bind_texture(glow::TEXTURE_2D, Some(velocity_texture.texture));
+                        self.context.active_texture(glow::TEXTURE1);
+                        self.context.bind_texture(
+                            glow::TEXTURE_2D,
+                            Some(self.forward_advection_texture.texture),
+                        );
+                        self.bind_backward_advection_texture();
+
+                        self.context
+                            .draw_elements(glow::TRIANGLES, 6, glow::UNSIGNED_SHORT, 0);
+                    });
+            }
+
+            AdvectionScheme::Bfecc => {
+                self.trace_round_trip();
+
+                // The forward step isn’t needed anymore, so reuse its texture
+                // for the corrected velocity.
+                let velocity_texture = self.velocity_textures.current();
+                self.forward_advection_texture
+                    .draw_to(&self.context, || unsafe {
+                        self.bfecc_pass.use_program();
+
+                        self.context.active_texture(glow::TEXTURE0);
+                        self.context
+                            .bind_texture(glow::TEXTURE_2D, Some(velocity_texture.texture));
+                        self.bind_backward_advection_texture();
+
+                        self.context
+                            .draw_elements(glow::TRIANGLES, 6, glow::UNSIGNED_SHORT, 0);
+                    });
+                drop(velocity_texture);
+
+                self.velocity_textures
+                    .draw_to(&self.context, |velocity_texture| {
+                        self.advect_with(
+                            &self.forward_advection_texture,
+                            velocity_texture,
+                            1.0,
+                            true,
+                        );
+                    });
+            }
+        }
+    }
+
+    // Take a semi-Lagrangian step forward into `forward_advection_texture`,
+    // and trace it back again into `backward_advection_texture`. The
+    // difference between the start and the end of the round trip is twice the
+    // error of a single step.
+    fn trace_round_trip(&self) -> () {
+        let velocity_texture = self.velocity_textures.current();
+
+        self.forward_advection_texture.draw_to(&self.context, || {
+            self.advect_with(&velocity_texture, &velocity_texture, 1.0, false);
+        });
+        self.backward_advection_texture.draw_to(&self.context, || {
+            self.advect_with(
+                &self.forward_advection_texture,
+                &velocity_texture,
+                -1.0,
+                false,
+            );
+        });
+    }
+
+    // Draw `input_texture` moved along `velocity_texture`. A negative direction
+    // moves it forward in time instead of back.
+    fn advect_with(
+        &self,
+        input_texture: &Framebuffer,
+        velocity_texture: &Framebuffer,
+        direction: f32,
+        limit: bool,
+    ) -> () {
+        self.advection_pass.set_uniforms(&[
+            &Uniform {
+                name: "uDirection",
+                value: UniformValue::Float(direction),
+            },
+            &Uniform {
+                name: "uLimit",
+                value: UniformValue::UnsignedInt(limit as u32),
+            },
+        ]);
+
+        unsafe {
+            self.context.active_texture(glow::TEXTURE0);
+            self.context
+                .bind_texture(glow::TEXTURE_2D, Some(input_texture.texture));
+            self.context.active_texture(glow::TEXTURE1);
+            self.context
+                .bind_texture(glow::TEXTURE_2D, Some(velocity_texture.texture));
+
+            self.context
+                .draw_elements(glow::TRIANGLES, 6, glow::UNSIGNED_SHORT, 0);
+        }
+    }
+
+    fn bind_backward_advection_texture(&self) -> () {
+        unsafe {
+            self.context
+                .active_texture(glow::TEXTURE0 + BACKWARD_TEXTURE_UNIT);
+            self.context.bind_texture(
+                glow::TEXTURE_2D,
+                Some(self.backward_advection_texture.texture),
+            );
+        }
     }
 
     pub fn diffuse(&self, timestep: f32) -> () {
@@ -388,6 +1121,47 @@ impl Fluid {
         }
     }
 
+    // Vorticity confinement
+    //
+    // Put back some of the small swirls smoothed out by advection and
+    // diffusion. Skipped entirely when `vorticity_strength` is zero.
+    pub fn confine_vorticity(&self) -> () {
+        if self.settings.vorticity_strength <= 0.0 {
+            return;
+        }
+
+        self.curl_texture.draw_to(&self.context, || unsafe {
+            self.curl_pass.use_program();
+
+            self.context.active_texture(glow::TEXTURE0);
+            self.context.bind_texture(
+                glow::TEXTURE_2D,
+                Some(self.velocity_textures.current().texture),
+            );
+
+            self.context
+                .draw_elements(glow::TRIANGLES, 6, glow::UNSIGNED_SHORT, 0);
+        });
+
+        self.vorticity_pass.set_uniform(&Uniform {
+            name: "uVorticityStrength",
+            value: UniformValue::Float(self.settings.vorticity_strength),
+        });
+
+        self.velocity_textures
+            .draw_to(&self.context, |velocity_texture| unsafe {
+                self.context.active_texture(glow::TEXTURE0);
+                self.context
+                    .bind_texture(glow::TEXTURE_2D, Some(velocity_texture.texture));
+                self.context.active_texture(glow::TEXTURE1);
+                self.context
+                    .bind_texture(glow::TEXTURE_2D, Some(self.curl_texture.texture));
+
+                self.context
+                    .draw_elements(glow::TRIANGLES, 6, glow::UNSIGNED_SHORT, 0);
+            });
+    }
+
     pub fn calculate_divergence(&self) -> () {
         self.divergence_texture.draw_to(&self.context, || unsafe {
             self.divergence_pass.use_program();
@@ -404,13 +1178,51 @@ impl Fluid {
     }
 
     pub fn solve_pressure(&self) -> () {
+        // Otherwise, the pressure from the last step is a good first guess.
+        if !self.settings.warm_start_pressure {
+            self.pressure_textures
+                .clear_color_with(&[self.settings.starting_pressure, 0.0, 0.0, 1.0])
+                .unwrap();
+        }
+
+        match self.settings.pressure_solver {
+            PressureSolver::Jacobi => {
+                let mut remaining_iterations = self.settings.pressure_iterations;
+
+                while remaining_iterations > 0 {
+                    let iterations = if self.settings.pressure_tolerance > 0.0 {
+                        remaining_iterations.min(PRESSURE_CHECK_INTERVAL)
+                    } else {
+                        remaining_iterations
+                    };
+
+                    self.solve_pressure_with_jacobi(iterations);
+                    remaining_iterations -= iterations;
+
+                    if remaining_iterations > 0 && self.has_pressure_converged() {
+                        break;
+                    }
+                }
+            }
+            PressureSolver::Multigrid {
+                cycles,
+                smoothing_iterations,
+            } => {
+                for cycle in 1..=cycles {
+                    self.v_cycle(smoothing_iterations);
+
+                    if cycle < cycles && self.has_pressure_converged() {
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    fn solve_pressure_with_jacobi(&self, iterations: u32) -> () {
         let alpha = -self.grid_size * self.grid_size;
         let r_beta = 0.25;
 
-        self.pressure_textures
-            .clear_color_with(&[self.settings.starting_pressure, 0.0, 0.0, 1.0])
-            .unwrap();
-
         self.pressure_pass.set_uniforms(&[
             &Uniform {
                 name: "alpha",
@@ -428,7 +1240,7 @@ impl Fluid {
                 .bind_texture(glow::TEXTURE_2D, Some(self.divergence_texture.texture));
         }
 
-        for _ in 0..self.settings.pressure_iterations {
+        for _ in 0..iterations {
             self.pressure_textures
                 .draw_to(&self.context, |pressure_texture| unsafe {
                     self.context.active_texture(glow::TEXTURE1);
@@ -441,6 +1253,223 @@ impl Fluid {
         }
     }
 
+    // Multigrid V-cycle
+    //
+    // Smooth the pressure, then solve for the remaining error on a grid half
+    // the size, recursively, and add the correction back. Jacobi iterations
+    // only fix the error between neighbouring cells, so the coarser grids take
+    // care of the smooth, large-scale error.
+    fn v_cycle(&self, smoothing_iterations: u32) -> () {
+        let level_count = self.multigrid_levels.len();
+
+        for level in 0..level_count {
+            let grid = self.pressure_grid(level);
+            let coarse_grid = &self.multigrid_levels[level];
+
+            self.set_texel_size(grid.texel_size);
+            self.relax_pressure(&grid, smoothing_iterations);
+            self.calculate_residual(&grid);
+
+            // Restrict the residual to the coarser grid. The linear filter
+            // averages the 4 fine cells under each coarse cell.
+            self.resampler
+                .resample(grid.residual_texture, &coarse_grid.divergence_texture);
+            self.bind_shared_state();
+            coarse_grid.pressure_textures.zero_out().unwrap();
+        }
+
+        let coarsest_grid = self.pressure_grid(level_count);
+        self.set_texel_size(coarsest_grid.texel_size);
+        self.relax_pressure(&coarsest_grid, COARSEST_MULTIGRID_ITERATIONS);
+
+        for level in (0..level_count).rev() {
+            let grid = self.pressure_grid(level);
+            let correction = self.multigrid_levels[level].pressure_textures.current();
+
+            self.set_texel_size(grid.texel_size);
+            self.prolongate_pass.use_program();
+            grid.pressure_textures
+                .draw_to(&self.context, |pressure_texture| unsafe {
+                    self.context.active_texture(glow::TEXTURE0);
+                    self.context
+                        .bind_texture(glow::TEXTURE_2D, Some(pressure_texture.texture));
+                    self.context.active_texture(glow::TEXTURE1);
+                    self.context
+                        .bind_texture(glow::TEXTURE_2D, Some(correction.texture));
+
+                    self.context
+                        .draw_elements(glow::TRIANGLES, 6, glow::UNSIGNED_SHORT, 0);
+                });
+
+            self.relax_pressure(&grid, smoothing_iterations);
+        }
+
+        self.set_texel_size(self.texel_size);
+    }
+
+    fn pressure_grid(&self, level: usize) -> PressureGrid {
+        match level.checked_sub(1) {
+            None => PressureGrid {
+                texel_size: self.texel_size,
+                grid_size: self.grid_size,
+                divergence_texture: &self.divergence_texture,
+                pressure_textures: &self.pressure_textures,
+                residual_texture: &self.residual_texture,
+            },
+            Some(index) => {
+                let level = &self.multigrid_levels[index];
+                PressureGrid {
+                    texel_size: level.texel_size,
+                    grid_size: level.grid_size,
+                    divergence_texture: &level.divergence_texture,
+                    pressure_textures: &level.pressure_textures,
+                    residual_texture: &level.residual_texture,
+                }
+            }
+        }
+    }
+
+    fn relax_pressure(&self, grid: &PressureGrid, iterations: u32) -> () {
+        self.relax_pressure_pass.set_uniform(&Uniform {
+            name: "alpha",
+            value: UniformValue::Float(-grid.grid_size * grid.grid_size),
+        });
+
+        unsafe {
+            self.context.active_texture(glow::TEXTURE0);
+            self.context
+                .bind_texture(glow::TEXTURE_2D, Some(grid.divergence_texture.texture));
+        }
+
+        for _ in 0..iterations {
+            grid.pressure_textures
+                .draw_to(&self.context, |pressure_texture| unsafe {
+                    self.context.active_texture(glow::TEXTURE1);
+                    self.context
+                        .bind_texture(glow::TEXTURE_2D, Some(pressure_texture.texture));
+
+                    self.context
+                        .draw_elements(glow::TRIANGLES, 6, glow::UNSIGNED_SHORT, 0);
+                });
+        }
+    }
+
+    fn calculate_residual(&self, grid: &PressureGrid) -> () {
+        self.pressure_residual_pass.set_uniform(&Uniform {
+            name: "alpha",
+            value: UniformValue::Float(-grid.grid_size * grid.grid_size),
+        });
+
+        grid.residual_texture.draw_to(&self.context, || unsafe {
+            self.context.active_texture(glow::TEXTURE0);
+            self.context
+                .bind_texture(glow::TEXTURE_2D, Some(grid.divergence_texture.texture));
+            self.context.active_texture(glow::TEXTURE1);
+            self.context.bind_texture(
+                glow::TEXTURE_2D,
+                Some(grid.pressure_textures.current().texture),
+            );
+
+            self.context
+                .draw_elements(glow::TRIANGLES, 6, glow::UNSIGNED_SHORT, 0);
+        });
+    }
+
+    // Read back everything needed to pick up the simulation where it left
+    // off. This waits on the GPU.
+    pub fn snapshot(&self) -> Result<FluidState, render::Problem> {
+        Ok(FluidState {
+            width: self.width,
+            height: self.height,
+            velocity: self.velocity_textures.current().read_data()?,
+            pressure: self.pressure_textures.current().read_data()?,
+            dye: self.dye_textures.current().read_data()?,
+            temperature: self.temperature_textures.current().read_data()?,
+        })
+    }
+
+    // Load a snapshot. Snapshots taken at a different size are stretched to
+    // fit, like in `resize`.
+    // Check that a snapshot holds every field at the size it claims, without
+    // changing anything.
+    pub fn check_state(&self, state: &FluidState) -> Result<(), render::Problem> {
+        for (textures, data) in self.state_fields(state) {
+            textures
+                .current()
+                .check_resampled_f32_data(state.width, state.height, data)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn restore(&self, state: &FluidState) -> Result<(), render::Problem> {
+        self.check_state(state)?;
+
+        for (textures, data) in self.state_fields(state) {
+            textures.current().write_resampled_f32_data(
+                &self.resampler,
+                state.width,
+                state.height,
+                data,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    // Pair each field in a snapshot with the textures it goes into.
+    fn state_fields<'a>(
+        &'a self,
+        state: &'a FluidState,
+    ) -> [(&'a DoubleFramebuffer, &'a [f32]); 4] {
+        [
+            (&self.velocity_textures, &state.velocity),
+            (&self.pressure_textures, &state.pressure),
+            (&self.dye_textures, &state.dye),
+            (&self.temperature_textures, &state.temperature),
+        ]
+    }
+
+    // The root mean square of the residual of the last pressure solve. Lower
+    // is more incompressible. This reads back from the GPU, so it’s meant for
+    // tuning and debugging, not for every frame.
+    pub fn pressure_residual(&self) -> f32 {
+        self.bind_shared_state();
+        self.calculate_residual(&self.pressure_grid(0));
+
+        let residual = self.residual_texture.read_f32_data();
+        let sum_of_squares: f32 = residual.chunks_exact(4).map(|texel| texel[0].powi(2)).sum();
+        (sum_of_squares / (self.width * self.height) as f32).sqrt()
+    }
+
+    fn has_pressure_converged(&self) -> bool {
+        self.settings.pressure_tolerance > 0.0
+            && self.estimate_pressure_residual() < self.settings.pressure_tolerance
+    }
+
+    // A cheaper `pressure_residual` for checking convergence during the solve.
+    //
+    // The residual pass also writes the squared residual to the second
+    // channel. Resampling it down through the multigrid levels averages it,
+    // roughly, so only the handful of texels in the coarsest level have to be
+    // read back.
+    fn estimate_pressure_residual(&self) -> f32 {
+        self.calculate_residual(&self.pressure_grid(0));
+
+        let mut residual_texture = &self.residual_texture;
+        for level in self.multigrid_levels.iter() {
+            self.resampler
+                .resample(residual_texture, &level.residual_texture);
+            residual_texture = &level.residual_texture;
+        }
+        self.bind_shared_state();
+
+        let residual = residual_texture.read_f32_data();
+        let sum_of_squares: f32 = residual.chunks_exact(4).map(|texel| texel[1]).sum();
+        let texel_count = residual_texture.width * residual_texture.height;
+        (sum_of_squares / texel_count as f32).sqrt()
+    }
+
     pub fn subtract_gradient(&self) -> () {
         self.subtract_gradient_pass.use_program();
 
@@ -460,17 +1489,88 @@ impl Fluid {
             });
     }
 
-    #[allow(dead_code)]
+    // Carry the dye along with the velocity. Does nothing if the dye is off.
+    pub fn advect_dye(&self) -> () {
+        if let Some(dye) = &self.settings.dye {
+            self.advect_quantity(&self.dye_textures, dye.dissipation);
+        }
+    }
+
+    // Carry the temperature along with the velocity. Does nothing if the
+    // temperature is off.
+    pub fn advect_temperature(&self) -> () {
+        if let Some(temperature) = &self.settings.temperature {
+            self.advect_quantity(&self.temperature_textures, temperature.dissipation);
+        }
+    }
+
+    fn advect_quantity(&self, textures: &DoubleFramebuffer, dissipation: f32) -> () {
+        self.advect_quantity_pass.set_uniform(&Uniform {
+            name: "uDissipation",
+            value: UniformValue::Float(dissipation),
+        });
+
+        textures.draw_to(&self.context, |quantity_texture| unsafe {
+            self.context.active_texture(glow::TEXTURE0);
+            self.context.bind_texture(
+                glow::TEXTURE_2D,
+                Some(self.velocity_textures.current().texture),
+            );
+            self.context.active_texture(glow::TEXTURE1);
+            self.context
+                .bind_texture(glow::TEXTURE_2D, Some(quantity_texture.texture));
+
+            self.context
+                .draw_elements(glow::TRIANGLES, 6, glow::UNSIGNED_SHORT, 0);
+        });
+    }
+
+    // Push hot fluid up and cold fluid down. Does nothing if the temperature
+    // is off.
+    pub fn apply_buoyancy(&self) -> () {
+        let temperature = match &self.settings.temperature {
+            Some(temperature) => temperature,
+            None => return,
+        };
+
+        self.buoyancy_pass.set_uniform(&Uniform {
+            name: "uBuoyancy",
+            value: UniformValue::Float(temperature.buoyancy),
+        });
+
+        self.velocity_textures
+            .draw_to(&self.context, |velocity_texture| unsafe {
+                self.context.active_texture(glow::TEXTURE0);
+                self.context
+                    .bind_texture(glow::TEXTURE_2D, Some(velocity_texture.texture));
+                self.context.active_texture(glow::TEXTURE1);
+                self.context.bind_texture(
+                    glow::TEXTURE_2D,
+                    Some(self.temperature_textures.current().texture),
+                );
+
+                self.context
+                    .draw_elements(glow::TRIANGLES, 6, glow::UNSIGNED_SHORT, 0);
+            });
+    }
+
     pub fn get_velocity(&self) -> Ref<Framebuffer> {
         self.velocity_textures.current()
     }
 
-    #[allow(dead_code)]
     pub fn get_divergence(&self) -> &Framebuffer {
         &self.divergence_texture
     }
 
     #[allow(dead_code)]
+    pub fn get_curl(&self) -> &Framebuffer {
+        &self.curl_texture
+    }
+
+    pub fn get_obstacles(&self) -> &Framebuffer {
+        &self.obstacle_texture
+    }
+
     pub fn get_pressure(&self) -> Ref<Framebuffer> {
         self.pressure_textures.current()
     }
@@ -479,9 +1579,83 @@ impl Fluid {
     pub fn get_velocity_textures(&self) -> &DoubleFramebuffer {
         &self.velocity_textures
     }
+
+    pub fn get_dye(&self) -> Ref<'_, Framebuffer> {
+        self.dye_textures.current()
+    }
+
+    pub fn get_dye_textures(&self) -> &DoubleFramebuffer {
+        &self.dye_textures
+    }
+
+    pub fn get_temperature_textures(&self) -> &DoubleFramebuffer {
+        &self.temperature_textures
+    }
+}
+
+// Name the values of `Boundary::to_uniform` for the shaders.
+fn boundary_defines() -> [Define<'static>; 4] {
+    [
+        ("FREE_SLIP", Boundary::FreeSlip.to_uniform().to_string()),
+        ("NO_SLIP", Boundary::NoSlip.to_uniform().to_string()),
+        ("PERIODIC", Boundary::Periodic.to_uniform().to_string()),
+        ("OPEN", Boundary::Open.to_uniform().to_string()),
+    ]
+}
+
+fn create_multigrid_levels(
+    context: &Context,
+    width: u32,
+    height: u32,
+    grid_size: f32,
+    field_format: FieldFormat,
+) -> Result<Vec<MultigridLevel>, render::Problem> {
+    let options = field_format.field_options();
+
+    compute_multigrid_sizes(width, height, grid_size)
+        .into_iter()
+        .map(|(width, height, texel_size, grid_size)| {
+            let data = vec![0.0; (2 * width * height) as usize];
+
+            Ok(MultigridLevel {
+                texel_size,
+                grid_size,
+                divergence_texture: render::Framebuffer::new(&context, width, height, options)?
+                    .with_f32_data(&data)?,
+                pressure_textures: render::DoubleFramebuffer::new(
+                    &context, width, height, options,
+                )?
+                .with_f32_data(&data)?,
+                residual_texture: render::Framebuffer::new(&context, width, height, options)?
+                    .with_f32_data(&data)?,
+            })
+        })
+        .collect()
+}
+
+// The width, height, texel size, and grid size of each coarser multigrid
+// level, starting with the one right below the full-size grid.
+pub fn compute_multigrid_sizes(
+    width: u32,
+    height: u32,
+    grid_size: f32,
+) -> Vec<(u32, u32, [f32; 2], f32)> {
+    let mut sizes = Vec::new();
+    let (mut width, mut height, mut grid_size) = (width, height, grid_size);
+
+    while width.min(height) >= 2 * MIN_MULTIGRID_SIZE {
+        width = width.div_ceil(2);
+        height = height.div_ceil(2);
+        grid_size *= 2.0;
+
+        let texel_size = [1.0 / width as f32, 1.0 / height as f32];
+        sizes.push((width, height, texel_size, grid_size));
+    }
+
+    sizes
 }
 
-fn compute_fluid_size(fluid_size: f32, ratio: f32) -> (u32, u32, [f32; 2]) {
+pub fn compute_fluid_size(fluid_size: f32, ratio: f32) -> (u32, u32, [f32; 2]) {
     let width = (fluid_size * ratio).round();
     let height = fluid_size;
     let texel_size = [1.0 / width, 1.0 / height];
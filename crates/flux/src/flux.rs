@@ -1,10 +1,15 @@
-use crate::{drawer, fluid, noise, render, settings};
-use drawer::Drawer;
-use fluid::Fluid;
+use crate::{drawer, fluid, noise, obstacle, readback, render, settings, snapshot};
+use drawer::{Drawer, DrawerOptions};
+use fluid::{Fluid, Splat};
 use noise::NoiseInjector;
+use obstacle::ObstacleMask;
+use readback::{Field, FieldData, FieldReadback};
+use render::{Capabilities, FieldFormat};
 use settings::Settings;
+use snapshot::FluxState;
 
 use glow::HasContext;
+use rustc_hash::FxHashMap;
 use std::fmt;
 use std::rc::Rc;
 
@@ -13,6 +18,10 @@ pub struct Flux {
     drawer: Drawer,
     noise_injector: NoiseInjector,
     settings: Rc<Settings>,
+    capabilities: Capabilities,
+    field_format: FieldFormat,
+    // The last known position of each active pointer, in world space
+    pointers: FxHashMap<u64, [f32; 2]>,
 
     pub context: render::Context,
     elapsed_time: f32,
@@ -23,15 +32,16 @@ pub struct Flux {
 }
 
 impl Flux {
-    pub fn update(&mut self, settings: &Rc<Settings>) -> () {
+    pub fn update(&mut self, settings: &Rc<Settings>) -> Result<(), Problem> {
         self.settings = Rc::clone(settings);
 
-        self.fluid.update(&self.settings);
+        self.fluid
+            .update(&self.settings)
+            .map_err(Problem::CannotRender)?;
         self.drawer.update(&self.settings);
         self.noise_injector
-            .update_channel(0, &self.settings.noise_channel_1);
-        self.noise_injector
-            .update_channel(1, &self.settings.noise_channel_2);
+            .set_channels(&self.settings.noise_channels)
+            .map_err(Problem::CannotRender)
     }
 
     pub fn new(
@@ -42,9 +52,36 @@ impl Flux {
         physical_height: u32,
         settings: &Rc<Settings>,
     ) -> Result<Flux, Problem> {
+        Self::with_field_format(
+            context,
+            logical_width,
+            logical_height,
+            physical_width,
+            physical_height,
+            settings,
+            None,
+        )
+    }
+
+    // Like `new`, but store the simulation fields in `field_format`, rather
+    // than the most precise format the context supports. `None` picks the
+    // best one.
+    pub fn with_field_format(
+        context: &render::Context,
+        logical_width: u32,
+        logical_height: u32,
+        physical_width: u32,
+        physical_height: u32,
+        settings: &Rc<Settings>,
+        field_format: Option<FieldFormat>,
+    ) -> Result<Flux, Problem> {
+        let capabilities = Capabilities::probe(context);
+        let field_format = field_format.unwrap_or_else(|| FieldFormat::detect(&capabilities));
+
         let fluid_frame_time = 1.0 / settings.fluid_simulation_frame_rate;
         let ratio = logical_width as f32 / logical_height as f32;
-        let fluid = Fluid::new(&context, ratio, &settings).map_err(Problem::CannotRender)?;
+        let fluid =
+            Fluid::new(&context, ratio, &settings, field_format).map_err(Problem::CannotRender)?;
 
         let drawer = Drawer::new(
             &context,
@@ -53,17 +90,19 @@ impl Flux {
             physical_width,
             physical_height,
             &settings,
+            DrawerOptions {
+                capabilities: &capabilities,
+                field_format,
+            },
         )
         .map_err(Problem::CannotRender)?;
 
-        let mut noise_injector = NoiseInjector::new(&context, fluid.width / 4, fluid.height / 4)
-            .map_err(Problem::CannotRender)?;
+        let mut noise_injector =
+            NoiseInjector::new(&context, fluid.width / 4, fluid.height / 4, field_format)
+                .map_err(Problem::CannotRender)?;
 
         noise_injector
-            .add_noise(settings.noise_channel_1.clone())
-            .map_err(Problem::CannotRender)?;
-        noise_injector
-            .add_noise(settings.noise_channel_2.clone())
+            .set_channels(&settings.noise_channels)
             .map_err(Problem::CannotRender)?;
 
         Ok(Flux {
@@ -71,6 +110,9 @@ impl Flux {
             drawer,
             noise_injector,
             settings: Rc::clone(settings),
+            capabilities,
+            field_format,
+            pointers: FxHashMap::default(),
 
             context: Rc::clone(context),
             elapsed_time: 0.0,
@@ -81,6 +123,16 @@ impl Flux {
         })
     }
 
+    // What the context supports, for quality decisions and bug reports.
+    pub fn capabilities(&self) -> &Capabilities {
+        &self.capabilities
+    }
+
+    // How the simulation fields are stored on this device.
+    pub fn field_format(&self) -> FieldFormat {
+        self.field_format
+    }
+
     pub fn resize(
         &mut self,
         logical_width: u32,
@@ -91,6 +143,9 @@ impl Flux {
         let ratio = logical_width as f32 / logical_height as f32;
         self.fluid.resize(ratio).unwrap(); // fix
 
+        // World space changes with the size, so forget where the pointers were.
+        self.pointers.clear();
+
         self.drawer
             .resize(
                 logical_width,
@@ -106,29 +161,241 @@ impl Flux {
             .max_frame_time
             .min(0.001 * (timestamp - self.last_timestamp));
         self.last_timestamp = timestamp;
+
+        self.compute(timestep);
+        self.render();
+    }
+
+    // Advance the simulation by `timestep` seconds.
+    pub fn compute(&mut self, timestep: f32) {
         self.elapsed_time += timestep;
         self.frame_time += timestep;
 
         while self.frame_time >= self.fluid_frame_time {
             self.noise_injector.generate_all(self.elapsed_time);
+            if let Some(dye) = &self.settings.dye {
+                self.noise_injector.blend_quantity_into(
+                    self.fluid.get_dye_textures(),
+                    [dye.color.r, dye.color.g, dye.color.b, 1.0],
+                    dye.noise_amount,
+                    self.elapsed_time,
+                );
+            }
+            if let Some(temperature) = &self.settings.temperature {
+                self.noise_injector.blend_quantity_into(
+                    self.fluid.get_temperature_textures(),
+                    [1.0, 0.0, 0.0, 0.0],
+                    temperature.noise_amount,
+                    self.elapsed_time,
+                );
+            }
             self.noise_injector
                 .blend_noise_into(&self.fluid.get_velocity_textures(), self.elapsed_time);
 
             self.fluid.prepare_pass(self.fluid_frame_time);
+            self.fluid.apply_splats();
             self.fluid.advect();
             self.fluid.diffuse(self.fluid_frame_time); // <- Convection
+            self.fluid.apply_buoyancy();
+            self.fluid.confine_vorticity();
             self.fluid.calculate_divergence();
             self.fluid.solve_pressure();
             self.fluid.subtract_gradient();
+            self.fluid.advect_dye();
+            self.fluid.advect_temperature();
 
             self.frame_time -= self.fluid_frame_time;
         }
 
         // TODO: the line animation is still dependent on the client’s fps. Is
         // this worth fixing?
-        self.drawer
-            .place_lines(timestep, &self.fluid.get_velocity());
+        self.drawer.place_lines(
+            timestep,
+            &self.fluid.get_velocity(),
+            self.fluid.get_obstacles(),
+        );
+    }
+
+    // Add an image of obstacles on top of those in the settings, or remove it
+    // with `None`. See `ObstacleMask` for the format.
+    pub fn set_obstacle_mask(&mut self, mask: Option<ObstacleMask>) -> Result<(), Problem> {
+        self.fluid
+            .set_obstacle_mask(mask)
+            .map_err(Problem::CannotRender)
+    }
 
+    // Capture the state of the animation, so that it can be resumed later
+    // with `restore`. This reads back from the GPU, so don’t call it every
+    // frame.
+    pub fn snapshot(&self) -> Result<FluxState, Problem> {
+        Ok(FluxState {
+            elapsed_time: self.elapsed_time,
+            frame_time: self.frame_time,
+            fluid: self.fluid.snapshot().map_err(Problem::CannotRender)?,
+            noise: self
+                .noise_injector
+                .snapshot()
+                .map_err(Problem::CannotRender)?,
+            drawer: self.drawer.snapshot(),
+        })
+    }
+
+    // Resume the animation from a snapshot. It should be taken with the same
+    // settings. If the size of the screen has changed, the fluid is stretched
+    // to fit and the lines start afresh.
+    pub fn restore(&mut self, state: &FluxState) -> Result<(), Problem> {
+        // The number of lines depends on the size of the screen
+        let restores_lines = state.drawer.line_count == self.drawer.line_count;
+
+        // Check the whole snapshot first, so that a bad one changes nothing
+        self.noise_injector
+            .check_state(&state.noise)
+            .map_err(cannot_restore)?;
+        self.fluid
+            .check_state(&state.fluid)
+            .map_err(cannot_restore)?;
+        if restores_lines {
+            self.drawer
+                .check_state(&state.drawer)
+                .map_err(cannot_restore)?;
+        }
+
+        self.noise_injector
+            .restore(&state.noise)
+            .map_err(Problem::CannotRender)?;
+        self.fluid
+            .restore(&state.fluid)
+            .map_err(Problem::CannotRender)?;
+        if restores_lines {
+            self.drawer
+                .restore(&state.drawer)
+                .map_err(Problem::CannotRender)?;
+        }
+
+        self.elapsed_time = state.elapsed_time;
+        self.frame_time = state.frame_time;
+
+        Ok(())
+    }
+
+    // Start reading a field back from the GPU. This doesn’t wait for the
+    // data, so it’s cheap enough to call every frame.
+    pub fn request_field(&self, field: Field) -> Result<FieldReadback, Problem> {
+        let read = match field {
+            Field::Velocity => self.fluid.get_velocity().read_data_async(),
+            Field::Pressure => self.fluid.get_pressure().read_data_async(),
+            Field::Divergence => self.fluid.get_divergence().read_data_async(),
+            Field::Noise(channel_number) => self
+                .noise_injector
+                .get_noise_channel(channel_number)
+                .ok_or(Problem::NoSuchField(field))?
+                .read_data_async(),
+        }
+        .map_err(Problem::CannotRender)?;
+
+        Ok(FieldReadback { read })
+    }
+
+    // Read a field back from the GPU, waiting for it if needed.
+    pub fn read_field(&self, field: Field) -> Result<FieldData, Problem> {
+        Ok(self.request_field(field)?.finish())
+    }
+
+    // How far the last pressure solve was from making the fluid
+    // incompressible. Use it to compare pressure solvers and iteration counts.
+    // It reads back from the GPU, so don’t call it every frame.
+    pub fn pressure_residual(&self) -> f32 {
+        self.fluid.pressure_residual()
+    }
+
+    // Push the fluid in `direction` with a Gaussian splat centered on
+    // `position`. The position and radius are in world space, the same space
+    // the lines are laid out in. The splat is added on the next fluid step.
+    // Splats with non-finite values are ignored.
+    pub fn apply_force(
+        &mut self,
+        position: [f32; 2],
+        direction: [f32; 2],
+        radius: f32,
+        strength: f32,
+    ) -> () {
+        let length = (direction[0] * direction[0] + direction[1] * direction[1]).sqrt();
+        if !(length.is_finite() && length > 0.0) || !is_valid_splat(position, radius, strength) {
+            return;
+        }
+
+        self.fluid.add_splat(Splat {
+            position: self.drawer.to_fluid_space(position),
+            force: [
+                strength * direction[0] / length,
+                strength * direction[1] / length,
+            ],
+            vortex_strength: 0.0,
+            radius: self.drawer.to_fluid_distance(radius),
+        });
+    }
+
+    // Spin the fluid around `position`. Positive strengths turn
+    // counterclockwise. Takes the same units as `apply_force`.
+    pub fn apply_vortex(&mut self, position: [f32; 2], radius: f32, strength: f32) -> () {
+        if !is_valid_splat(position, radius, strength) {
+            return;
+        }
+
+        self.fluid.add_splat(Splat {
+            position: self.drawer.to_fluid_space(position),
+            force: [0.0, 0.0],
+            vortex_strength: strength,
+            radius: self.drawer.to_fluid_distance(radius),
+        });
+    }
+
+    // Convert a point in logical pixels, measured from the top-left corner,
+    // into world space.
+    pub fn to_world_space(&self, logical_x: f32, logical_y: f32) -> [f32; 2] {
+        self.drawer.to_world_space(logical_x, logical_y)
+    }
+
+    // Track a mouse cursor or touch point, identified by `id`, as it moves to
+    // a point in logical pixels. Each move pushes the fluid along the path of
+    // the pointer, harder the faster it moves, up to `pointer_strength`.
+    // Every tracked move pushes, so only call this while the pointer is
+    // pressed, and `release_pointer` when it lets go.
+    pub fn move_pointer(&mut self, id: u64, logical_x: f32, logical_y: f32) -> () {
+        let position = self.to_world_space(logical_x, logical_y);
+
+        if let Some(last_position) = self.pointers.insert(id, position) {
+            let direction = [
+                position[0] - last_position[0],
+                position[1] - last_position[1],
+            ];
+            let distance = (direction[0] * direction[0] + direction[1] * direction[1]).sqrt();
+            let radius = self.settings.pointer_radius;
+            let strength = self.settings.pointer_strength * (distance / radius).min(1.0);
+
+            self.apply_force(position, direction, radius, strength);
+        }
+    }
+
+    // Stop tracking a pointer, like when a finger is lifted or the cursor
+    // leaves the window.
+    pub fn release_pointer(&mut self, id: u64) -> () {
+        self.pointers.remove(&id);
+    }
+
+    // Spin up a vortex, twice the size of the pointer’s reach, at a point in
+    // logical pixels.
+    pub fn twirl_at(&mut self, logical_x: f32, logical_y: f32) -> () {
+        let position = self.to_world_space(logical_x, logical_y);
+        self.apply_vortex(
+            position,
+            2.0 * self.settings.pointer_radius,
+            4.0 * self.settings.pointer_strength,
+        );
+    }
+
+    // Draw the current state of the lines to the default framebuffer.
+    pub fn render(&self) {
         self.drawer.with_antialiasing(|| unsafe {
             self.context.clear_color(0.0, 0.0, 0.0, 1.0);
             self.context.clear(glow::COLOR_BUFFER_BIT);
@@ -139,15 +406,35 @@ impl Flux {
             // self.drawer.draw_texture(&self.fluid.get_velocity());
             // self.drawer.draw_texture(&self.fluid.get_pressure());
 
+            if let Some(dye) = &self.settings.dye {
+                self.drawer.draw_dye(&self.fluid.get_dye(), dye);
+            }
+
             self.drawer.draw_lines();
             self.drawer.draw_endpoints();
         });
     }
 }
 
+// NaN or infinity in a splat would spread through the velocity field and
+// never leave.
+fn is_valid_splat(position: [f32; 2], radius: f32, strength: f32) -> bool {
+    position[0].is_finite()
+        && position[1].is_finite()
+        && radius.is_finite()
+        && radius > 0.0
+        && strength.is_finite()
+}
+
+fn cannot_restore(problem: render::Problem) -> Problem {
+    Problem::CannotReadState(format!("The snapshot doesn’t fit: {}", problem))
+}
+
 #[derive(Debug)]
 pub enum Problem {
     CannotReadSettings(String),
+    CannotReadState(String),
+    NoSuchField(Field),
     CannotRender(render::Problem),
 }
 
@@ -156,6 +443,8 @@ impl fmt::Display for Problem {
         use Problem::*;
         match self {
             CannotReadSettings(msg) => write!(f, "{}", msg),
+            CannotReadState(msg) => write!(f, "{}", msg),
+            NoSuchField(field) => write!(f, "There’s no {:?} field", field),
             CannotRender(render_msg) => write!(f, "{}", render_msg.to_string()),
         }
     }
@@ -0,0 +1,395 @@
+use crate::{flux, obstacle, readback, render, settings, snapshot};
+use flux::Flux;
+use obstacle::ObstacleMask;
+use readback::{Field, FieldData};
+use render::{Capabilities, FieldFormat};
+use settings::Settings;
+use snapshot::FluxState;
+
+use glow::HasContext;
+use khronos_egl as egl;
+use std::mem::ManuallyDrop;
+use std::rc::Rc;
+use thiserror::Error;
+
+// https://www.khronos.org/registry/EGL/extensions/MESA/EGL_MESA_platform_surfaceless.txt
+const PLATFORM_SURFACELESS_MESA: egl::Enum = 0x31DD;
+
+type Egl = egl::DynamicInstance<egl::EGL1_4>;
+
+#[derive(Error, Debug)]
+pub enum Problem {
+    #[error("Cannot load EGL: {0}")]
+    CannotLoadEgl(String),
+
+    #[error("Cannot create an EGL display: {0}")]
+    CannotCreateDisplay(egl::Error),
+
+    #[error("Cannot find a suitable EGL config")]
+    NoSuitableConfig,
+
+    #[error("Cannot create an OpenGL context: {0}")]
+    CannotCreateContext(egl::Error),
+
+    #[error("Cannot create the offscreen surface: {0}")]
+    CannotCreateSurface(egl::Error),
+
+    #[error("{0}")]
+    CannotStartFlux(flux::Problem),
+
+    #[error("Cannot apply the settings: {0}")]
+    CannotUpdateSettings(flux::Problem),
+
+    #[error("Cannot set the obstacle mask: {0}")]
+    CannotSetObstacles(flux::Problem),
+
+    #[error("Cannot read the field: {0}")]
+    CannotReadField(flux::Problem),
+
+    #[error("Cannot take a snapshot: {0}")]
+    CannotTakeSnapshot(flux::Problem),
+
+    #[error("Cannot restore the snapshot: {0}")]
+    CannotRestoreSnapshot(flux::Problem),
+}
+
+// Run Flux without a window.
+//
+// This uses Mesa’s surfaceless EGL platform, which works on machines without a
+// display server, like build servers running llvmpipe. Frames are rendered
+// into an offscreen pbuffer, which acts as the default framebuffer, and read
+// back as RGBA pixels.
+pub struct HeadlessFlux {
+    pub width: u32,
+    pub height: u32,
+    pub frame_time: f32,
+
+    // Dropped by hand, so that it can delete its GL objects while the context
+    // is still current
+    flux: ManuallyDrop<Flux>,
+    context: Rc<glow::Context>,
+
+    session: EglSession,
+    config: egl::Config,
+}
+
+// The EGL display, context and surface behind a `HeadlessFlux`. The context
+// and surface are released when this is dropped, so that nothing leaks if
+// setting up fails halfway through.
+//
+// The display isn’t terminated. Every session in the process gets the same
+// display, and terminating it would break the other sessions.
+struct EglSession {
+    egl: Egl,
+    display: egl::Display,
+    context: Option<egl::Context>,
+    surface: Option<egl::Surface>,
+}
+
+impl EglSession {
+    fn make_current(&self, surface: egl::Surface) -> Result<(), Problem> {
+        self.egl
+            .make_current(self.display, Some(surface), Some(surface), self.context)
+            .map_err(Problem::CannotCreateContext)
+    }
+}
+
+impl Drop for EglSession {
+    fn drop(&mut self) {
+        self.egl.make_current(self.display, None, None, None).ok();
+        if let Some(surface) = self.surface.take() {
+            self.egl.destroy_surface(self.display, surface).ok();
+        }
+        if let Some(context) = self.context.take() {
+            self.egl.destroy_context(self.display, context).ok();
+        }
+    }
+}
+
+impl HeadlessFlux {
+    pub fn new(width: u32, height: u32, settings: &Rc<Settings>) -> Result<Self, Problem> {
+        Self::with_field_format(width, height, settings, None)
+    }
+
+    // Like `new`, but force the simulation fields into `field_format`, to try
+    // out the fallbacks for less capable GPUs. `None` picks the best one.
+    pub fn with_field_format(
+        width: u32,
+        height: u32,
+        settings: &Rc<Settings>,
+        field_format: Option<FieldFormat>,
+    ) -> Result<Self, Problem> {
+        let egl = unsafe { Egl::load_required() }
+            .map_err(|err| Problem::CannotLoadEgl(err.to_string()))?;
+        let display = get_display(&egl)?;
+        egl.initialize(display)
+            .map_err(Problem::CannotCreateDisplay)?;
+
+        let mut session = EglSession {
+            egl,
+            display,
+            context: None,
+            surface: None,
+        };
+        let config = choose_config(&session.egl, display)?;
+        session.context = Some(create_context(&session.egl, display, config)?);
+        let surface = create_surface(&session.egl, display, config, width, height)?;
+        session.surface = Some(surface);
+        session.make_current(surface)?;
+
+        let context = Rc::new(unsafe {
+            glow::Context::from_loader_function(|name| {
+                session
+                    .egl
+                    .get_proc_address(name)
+                    .map_or(std::ptr::null(), |address| address as *const _)
+            })
+        });
+
+        let flux = Flux::with_field_format(
+            &context,
+            width,
+            height,
+            width,
+            height,
+            settings,
+            field_format,
+        )
+        .map_err(Problem::CannotStartFlux)?;
+
+        Ok(Self {
+            width,
+            height,
+            frame_time: 1.0 / 60.0,
+
+            flux: ManuallyDrop::new(flux),
+            context,
+
+            session,
+            config,
+        })
+    }
+
+    pub fn update(&mut self, settings: &Rc<Settings>) -> Result<(), Problem> {
+        self.flux
+            .update(settings)
+            .map_err(Problem::CannotUpdateSettings)
+    }
+
+    pub fn capabilities(&self) -> &Capabilities {
+        self.flux.capabilities()
+    }
+
+    pub fn field_format(&self) -> FieldFormat {
+        self.flux.field_format()
+    }
+
+    // Change the size of the frame. Flux is resized like a window would be, so
+    // the fluid is stretched to fit and the lines start afresh.
+    pub fn resize(&mut self, width: u32, height: u32) -> Result<(), Problem> {
+        let session = &mut self.session;
+        let surface = create_surface(&session.egl, session.display, self.config, width, height)?;
+        if let Err(problem) = session.make_current(surface) {
+            session.egl.destroy_surface(session.display, surface).ok();
+            return Err(problem);
+        }
+        if let Some(old_surface) = session.surface.replace(surface) {
+            session
+                .egl
+                .destroy_surface(session.display, old_surface)
+                .ok();
+        }
+
+        self.width = width;
+        self.height = height;
+        self.flux.resize(width, height, width, height);
+
+        Ok(())
+    }
+
+    pub fn apply_force(
+        &mut self,
+        position: [f32; 2],
+        direction: [f32; 2],
+        radius: f32,
+        strength: f32,
+    ) -> () {
+        self.flux.apply_force(position, direction, radius, strength);
+    }
+
+    pub fn apply_vortex(&mut self, position: [f32; 2], radius: f32, strength: f32) -> () {
+        self.flux.apply_vortex(position, radius, strength);
+    }
+
+    pub fn set_obstacle_mask(&mut self, mask: Option<ObstacleMask>) -> Result<(), Problem> {
+        self.flux
+            .set_obstacle_mask(mask)
+            .map_err(Problem::CannotSetObstacles)
+    }
+
+    pub fn pressure_residual(&self) -> f32 {
+        self.flux.pressure_residual()
+    }
+
+    pub fn read_field(&self, field: Field) -> Result<FieldData, Problem> {
+        self.flux
+            .read_field(field)
+            .map_err(Problem::CannotReadField)
+    }
+
+    pub fn snapshot(&self) -> Result<FluxState, Problem> {
+        self.flux.snapshot().map_err(Problem::CannotTakeSnapshot)
+    }
+
+    pub fn restore(&mut self, state: &FluxState) -> Result<(), Problem> {
+        self.flux
+            .restore(state)
+            .map_err(Problem::CannotRestoreSnapshot)
+    }
+
+    // Render a single frame and return its pixels.
+    pub fn render_frame(&mut self) -> Vec<u8> {
+        self.flux.compute(self.frame_time);
+        self.flux.render();
+        self.read_pixels()
+    }
+
+    // Render `count` frames, each `frame_time` seconds apart.
+    pub fn render_frames(&mut self, count: u32) -> Vec<Vec<u8>> {
+        (0..count).map(|_| self.render_frame()).collect()
+    }
+
+    // Read back the last frame as tightly-packed RGBA rows, top row first.
+    pub fn read_pixels(&self) -> Vec<u8> {
+        let row_length = 4 * self.width as usize;
+        let mut pixels = vec![0; row_length * self.height as usize];
+
+        unsafe {
+            self.context.bind_framebuffer(glow::READ_FRAMEBUFFER, None);
+            self.context.pixel_store_i32(glow::PACK_ALIGNMENT, 1);
+            self.context.read_pixels(
+                0,
+                0,
+                self.width as i32,
+                self.height as i32,
+                glow::RGBA,
+                glow::UNSIGNED_BYTE,
+                glow::PixelPackData::Slice(&mut pixels),
+            );
+        }
+
+        // OpenGL reads from the bottom up.
+        pixels
+            .chunks_exact(row_length)
+            .rev()
+            .flatten()
+            .copied()
+            .collect()
+    }
+}
+
+impl Drop for HeadlessFlux {
+    fn drop(&mut self) {
+        // The EGL session is released after this, once the fields are
+        // dropped.
+        unsafe { ManuallyDrop::drop(&mut self.flux) };
+    }
+}
+
+fn get_display(egl: &Egl) -> Result<egl::Display, Problem> {
+    // Prefer Mesa’s surfaceless platform, which doesn’t need X11 or Wayland.
+    let has_surfaceless_platform = egl
+        .query_string(None, egl::EXTENSIONS)
+        .map(|extensions| {
+            extensions
+                .to_string_lossy()
+                .contains("EGL_MESA_platform_surfaceless")
+        })
+        .unwrap_or(false);
+
+    if has_surfaceless_platform {
+        if let Some(egl1_5) = egl.upcast::<egl::EGL1_5>() {
+            return egl1_5
+                .get_platform_display(
+                    PLATFORM_SURFACELESS_MESA,
+                    egl::DEFAULT_DISPLAY,
+                    &[egl::ATTRIB_NONE],
+                )
+                .map_err(Problem::CannotCreateDisplay);
+        }
+    }
+
+    egl.get_display(egl::DEFAULT_DISPLAY)
+        .ok_or(Problem::CannotCreateDisplay(egl::Error::BadDisplay))
+}
+
+fn choose_config(egl: &Egl, display: egl::Display) -> Result<egl::Config, Problem> {
+    egl.choose_first_config(
+        display,
+        &[
+            egl::SURFACE_TYPE,
+            egl::PBUFFER_BIT,
+            egl::RENDERABLE_TYPE,
+            egl::OPENGL_BIT,
+            egl::RED_SIZE,
+            8,
+            egl::GREEN_SIZE,
+            8,
+            egl::BLUE_SIZE,
+            8,
+            egl::ALPHA_SIZE,
+            8,
+            egl::NONE,
+        ],
+    )
+    .map_err(Problem::CannotCreateContext)?
+    .ok_or(Problem::NoSuitableConfig)
+}
+
+// Flux draws to the default framebuffer, so we need a surface to back it, even
+// if it never makes it to a screen.
+fn create_surface(
+    egl: &Egl,
+    display: egl::Display,
+    config: egl::Config,
+    width: u32,
+    height: u32,
+) -> Result<egl::Surface, Problem> {
+    egl.create_pbuffer_surface(
+        display,
+        config,
+        &[
+            egl::WIDTH,
+            width as egl::Int,
+            egl::HEIGHT,
+            height as egl::Int,
+            egl::NONE,
+        ],
+    )
+    .map_err(Problem::CannotCreateSurface)
+}
+
+fn create_context(
+    egl: &Egl,
+    display: egl::Display,
+    config: egl::Config,
+) -> Result<egl::Context, Problem> {
+    egl.bind_api(egl::OPENGL_API)
+        .map_err(Problem::CannotCreateContext)?;
+
+    egl.create_context(
+        display,
+        config,
+        None,
+        &[
+            egl::CONTEXT_MAJOR_VERSION,
+            3,
+            egl::CONTEXT_MINOR_VERSION,
+            3,
+            egl::CONTEXT_OPENGL_PROFILE_MASK,
+            egl::CONTEXT_OPENGL_CORE_PROFILE_BIT,
+            egl::NONE,
+        ],
+    )
+    .map_err(Problem::CannotCreateContext)
+}
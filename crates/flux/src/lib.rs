@@ -1,9 +1,22 @@
+mod cpu_fluid;
 mod data;
 mod drawer;
 mod fluid;
 mod flux;
+#[cfg(feature = "headless")]
+pub mod headless;
 mod noise;
+mod obstacle;
+mod readback;
 mod render;
+mod snapshot;
 
+pub use cpu_fluid::CpuFluid;
 pub use flux::Flux;
+pub use obstacle::ObstacleMask;
+pub use readback::{Field, FieldData, FieldReadback};
+#[cfg(debug_assertions)]
+pub use render::live_gl_objects;
+pub use render::{Capabilities, FieldFormat};
+pub use snapshot::FluxState;
 pub mod settings;
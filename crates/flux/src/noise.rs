@@ -1,23 +1,27 @@
 use crate::{data, render, settings};
 use render::{
-    Buffer, Context, DoubleFramebuffer, Framebuffer, Program, TextureOptions, Uniform,
+    Buffer, Context, DoubleFramebuffer, FieldFormat, FieldResampler, Framebuffer, Program, Uniform,
     UniformValue, VertexArrayObject, VertexBufferLayout,
 };
-use settings::Noise;
+use settings::{Noise, NoiseKind};
 
 use bytemuck::{Pod, Zeroable};
 use glow::HasContext;
 use std::rc::Rc;
 
-static NOISE_VERT_SHADER: &'static str =
-    include_str!(concat!(env!("OUT_DIR"), "/shaders/noise.vert"));
-static SIMPLEX_NOISE_FRAG_SHADER: &'static str =
-    include_str!(concat!(env!("OUT_DIR"), "/shaders/simplex_noise.frag"));
-static BLEND_WITH_CURL: &'static str =
-    include_str!(concat!(env!("OUT_DIR"), "/shaders/blend_with_curl.frag"));
-static BLEND_WITH_WIGGLE: &'static str =
-    include_str!(concat!(env!("OUT_DIR"), "/shaders/blend_with_wiggle.frag"));
-
+static NOISE_VERT_SHADER: &'static str = include_str!("../shaders/noise.vert");
+static SIMPLEX_NOISE_FRAG_SHADER: &'static str = include_str!("../shaders/simplex_noise.frag");
+static PERLIN_NOISE_FRAG_SHADER: &'static str = include_str!("../shaders/perlin_noise.frag");
+static WORLEY_NOISE_FRAG_SHADER: &'static str = include_str!("../shaders/worley_noise.frag");
+static VALUE_NOISE_FRAG_SHADER: &'static str = include_str!("../shaders/value_noise.frag");
+static FBM_NOISE_FRAG_SHADER: &'static str = include_str!("../shaders/fbm_noise.frag");
+static DOMAIN_WARP_NOISE_FRAG_SHADER: &'static str =
+    include_str!("../shaders/domain_warp_noise.frag");
+static BLEND_WITH_CURL: &'static str = include_str!("../shaders/blend_with_curl.frag");
+static BLEND_WITH_WIGGLE: &'static str = include_str!("../shaders/blend_with_wiggle.frag");
+static BLEND_QUANTITY: &'static str = include_str!("../shaders/blend_quantity.frag");
+
+// Must match `shaders/common/noise_uniforms.glsl`
 #[repr(C)]
 #[derive(Clone, Copy, Pod, Zeroable)]
 pub struct NoiseUniforms {
@@ -40,13 +44,36 @@ pub struct NoiseChannel {
     uniforms: Buffer,
 }
 
+// Where a noise channel is in its cycle, along with the last noise it
+// generated, packed like `Framebuffer::read_data`.
+#[derive(Clone, Debug)]
+pub struct NoiseChannelState {
+    pub blend_begin_time: f32,
+    pub last_blend_progress: f32,
+    pub offset1: f32,
+    pub offset2: f32,
+    pub texture: Vec<f32>,
+}
+
+// The state of every noise channel. The noise textures are `width` by
+// `height`.
+#[derive(Clone, Debug)]
+pub struct NoiseState {
+    pub width: u32,
+    pub height: u32,
+    pub channels: Vec<NoiseChannelState>,
+}
+
 impl NoiseChannel {
     pub fn tick(&mut self, context: &Context, elapsed_time: f32) -> () {
         self.blend_begin_time = elapsed_time;
         self.last_blend_progress = 0.0;
         self.offset1 += self.noise.offset_increment;
         self.offset2 += self.noise.offset_increment;
+        self.upload_offsets(context);
+    }
 
+    fn upload_offsets(&self, context: &Context) -> () {
         unsafe {
             context.bind_buffer(glow::UNIFORM_BUFFER, Some(self.uniforms.id));
             context.buffer_sub_data_u8_slice(
@@ -57,6 +84,12 @@ impl NoiseChannel {
             context.bind_buffer(glow::UNIFORM_BUFFER, None);
         }
     }
+
+    // How far along the channel is in blending its noise into the fluid, from
+    // 0 to 1.
+    fn blend_progress(&self, elapsed_time: f32) -> f32 {
+        ((elapsed_time - self.blend_begin_time) / self.noise.blend_duration).clamp(0.0, 1.0)
+    }
 }
 
 pub struct NoiseInjector {
@@ -64,11 +97,19 @@ pub struct NoiseInjector {
     pub channels: Vec<NoiseChannel>,
     width: u32,
     height: u32,
-    generate_noise_pass: Program,
+    field_format: FieldFormat,
+    simplex_noise_pass: Program,
+    perlin_noise_pass: Program,
+    worley_noise_pass: Program,
+    value_noise_pass: Program,
+    fbm_noise_pass: Program,
+    domain_warp_noise_pass: Program,
     blend_with_curl_pass: Program,
     blend_with_wiggle_pass: Program,
+    blend_quantity_pass: Program,
 
     noise_buffer: VertexArrayObject,
+    resampler: FieldResampler,
 }
 
 impl NoiseInjector {
@@ -99,7 +140,29 @@ impl NoiseInjector {
         }
     }
 
-    pub fn new(context: &Context, width: u32, height: u32) -> Result<Self, render::Problem> {
+    // Match the channels to the settings. Existing channels are reconfigured in
+    // place, so they keep their noise texture and blend progress. Extra
+    // channels are dropped and missing ones are added.
+    pub fn set_channels(&mut self, noise_channels: &[Noise]) -> Result<(), render::Problem> {
+        self.channels.truncate(noise_channels.len());
+
+        for (channel_number, noise) in noise_channels.iter().enumerate() {
+            if channel_number < self.channels.len() {
+                self.update_channel(channel_number, noise);
+            } else {
+                self.add_noise(noise.clone())?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn new(
+        context: &Context,
+        width: u32,
+        height: u32,
+        field_format: FieldFormat,
+    ) -> Result<Self, render::Problem> {
         // Geometry
         let plane_vertices = Buffer::from_f32(
             &context,
@@ -114,11 +177,60 @@ impl NoiseInjector {
             glow::STATIC_DRAW,
         )?;
 
-        let simplex_noise_program =
-            Program::new(&context, (NOISE_VERT_SHADER, SIMPLEX_NOISE_FRAG_SHADER))?;
-        let blend_with_curl_program = Program::new(&context, (NOISE_VERT_SHADER, BLEND_WITH_CURL))?;
-        let blend_with_wiggle_program =
-            Program::new(&context, (NOISE_VERT_SHADER, BLEND_WITH_WIGGLE))?;
+        let simplex_noise_program = Program::new_for_fields(
+            &context,
+            (NOISE_VERT_SHADER, SIMPLEX_NOISE_FRAG_SHADER),
+            &[],
+            field_format,
+        )?;
+        let perlin_noise_program = Program::new_for_fields(
+            &context,
+            (NOISE_VERT_SHADER, PERLIN_NOISE_FRAG_SHADER),
+            &[],
+            field_format,
+        )?;
+        let worley_noise_program = Program::new_for_fields(
+            &context,
+            (NOISE_VERT_SHADER, WORLEY_NOISE_FRAG_SHADER),
+            &[],
+            field_format,
+        )?;
+        let value_noise_program = Program::new_for_fields(
+            &context,
+            (NOISE_VERT_SHADER, VALUE_NOISE_FRAG_SHADER),
+            &[],
+            field_format,
+        )?;
+        let fbm_noise_program = Program::new_for_fields(
+            &context,
+            (NOISE_VERT_SHADER, FBM_NOISE_FRAG_SHADER),
+            &[("MAX_OCTAVES", settings::MAX_FBM_OCTAVES.to_string())],
+            field_format,
+        )?;
+        let domain_warp_noise_program = Program::new_for_fields(
+            &context,
+            (NOISE_VERT_SHADER, DOMAIN_WARP_NOISE_FRAG_SHADER),
+            &[],
+            field_format,
+        )?;
+        let blend_with_curl_program = Program::new_for_fields(
+            &context,
+            (NOISE_VERT_SHADER, BLEND_WITH_CURL),
+            &[],
+            field_format,
+        )?;
+        let blend_with_wiggle_program = Program::new_for_fields(
+            &context,
+            (NOISE_VERT_SHADER, BLEND_WITH_WIGGLE),
+            &[],
+            field_format,
+        )?;
+        let blend_quantity_program = Program::new_for_fields(
+            &context,
+            (NOISE_VERT_SHADER, BLEND_QUANTITY),
+            &[],
+            field_format,
+        )?;
 
         let noise_buffer = VertexArrayObject::new(
             &context,
@@ -135,14 +247,26 @@ impl NoiseInjector {
             Some(&plane_indices),
         )?;
 
-        simplex_noise_program.set_uniform_block("NoiseUniforms", 3);
+        let noise_programs = [
+            &simplex_noise_program,
+            &perlin_noise_program,
+            &worley_noise_program,
+            &value_noise_program,
+            &fbm_noise_program,
+            &domain_warp_noise_program,
+        ];
+
+        for noise_program in noise_programs {
+            noise_program.set_uniform_block("NoiseUniforms", 3);
+            noise_program.set_uniform(&Uniform {
+                name: "uResolution",
+                value: UniformValue::Vec2(&[width as f32, height as f32]),
+            });
+        }
+
         blend_with_curl_program.set_uniform_block("NoiseUniforms", 3);
         blend_with_wiggle_program.set_uniform_block("NoiseUniforms", 3);
-
-        simplex_noise_program.set_uniform(&Uniform {
-            name: "uResolution",
-            value: UniformValue::Vec2(&[width as f32, height as f32]),
-        });
+        blend_quantity_program.set_uniform_block("NoiseUniforms", 3);
 
         blend_with_curl_program.set_uniforms(&[
             &Uniform {
@@ -164,17 +288,35 @@ impl NoiseInjector {
                 value: UniformValue::Texture2D(1),
             },
         ]);
+        blend_quantity_program.set_uniforms(&[
+            &Uniform {
+                name: "inputTexture",
+                value: UniformValue::Texture2D(0),
+            },
+            &Uniform {
+                name: "noiseTexture",
+                value: UniformValue::Texture2D(1),
+            },
+        ]);
 
         Ok(Self {
             context: Rc::clone(context),
             channels: Vec::new(),
             width,
             height,
-            generate_noise_pass: simplex_noise_program,
+            field_format,
+            simplex_noise_pass: simplex_noise_program,
+            perlin_noise_pass: perlin_noise_program,
+            worley_noise_pass: worley_noise_program,
+            value_noise_pass: value_noise_program,
+            fbm_noise_pass: fbm_noise_program,
+            domain_warp_noise_pass: domain_warp_noise_program,
             blend_with_curl_pass: blend_with_curl_program,
             blend_with_wiggle_pass: blend_with_wiggle_program,
+            blend_quantity_pass: blend_quantity_program,
 
             noise_buffer,
+            resampler: FieldResampler::new(context, field_format)?,
         })
     }
 
@@ -183,12 +325,7 @@ impl NoiseInjector {
             &self.context,
             self.width,
             self.height,
-            TextureOptions {
-                mag_filter: glow::LINEAR,
-                min_filter: glow::LINEAR,
-                format: glow::RG32F,
-                ..Default::default()
-            },
+            self.field_format.field_options(),
         )?
         .with_f32_data(&vec![0.0; (self.width * self.height * 2) as usize])?;
 
@@ -223,34 +360,71 @@ impl NoiseInjector {
     }
 
     pub fn generate_all(&mut self, elapsed_time: f32) -> () {
-        for channel in self.channels.iter_mut() {
+        for channel_number in 0..self.channels.len() {
+            let channel = &self.channels[channel_number];
             let time_since_last_update = elapsed_time - channel.blend_begin_time;
 
             if time_since_last_update >= channel.noise.delay {
-                self.generate_noise_pass.use_program();
-
-                unsafe {
-                    self.context.bind_vertex_array(Some(self.noise_buffer.id));
-
-                    self.context.bind_buffer_base(
-                        glow::UNIFORM_BUFFER,
-                        3,
-                        Some(channel.uniforms.id),
-                    );
-
-                    channel.texture.draw_to(&self.context, || {
-                        self.context
-                            .draw_elements(glow::TRIANGLES, 6, glow::UNSIGNED_SHORT, 0);
-                    });
-                }
-
-                channel.tick(&self.context, elapsed_time);
+                self.generate_by_channel_number(channel_number, elapsed_time);
             }
         }
     }
+
     pub fn generate_by_channel_number(&mut self, channel_number: usize, elapsed_time: f32) {
         if let Some(channel) = self.channels.get_mut(channel_number) {
-            self.generate_noise_pass.use_program();
+            let noise_pass: &Program = match channel.noise.kind {
+                NoiseKind::Simplex => &self.simplex_noise_pass,
+                NoiseKind::Perlin => &self.perlin_noise_pass,
+                NoiseKind::Worley { jitter } => {
+                    self.worley_noise_pass.set_uniform(&Uniform {
+                        name: "uJitter",
+                        value: UniformValue::Float(jitter.clamp(0.0, 1.0)),
+                    });
+                    &self.worley_noise_pass
+                }
+                NoiseKind::Value => &self.value_noise_pass,
+                NoiseKind::Fbm {
+                    octaves,
+                    lacunarity,
+                    gain,
+                } => {
+                    self.fbm_noise_pass.set_uniforms(&[
+                        &Uniform {
+                            name: "uOctaves",
+                            value: UniformValue::SignedInt(
+                                octaves.clamp(1, settings::MAX_FBM_OCTAVES) as i32,
+                            ),
+                        },
+                        &Uniform {
+                            name: "uLacunarity",
+                            value: UniformValue::Float(lacunarity),
+                        },
+                        &Uniform {
+                            name: "uGain",
+                            value: UniformValue::Float(gain),
+                        },
+                    ]);
+                    &self.fbm_noise_pass
+                }
+                NoiseKind::DomainWarp {
+                    warp_frequency,
+                    warp_strength,
+                } => {
+                    self.domain_warp_noise_pass.set_uniforms(&[
+                        &Uniform {
+                            name: "uWarpFrequency",
+                            value: UniformValue::Float(warp_frequency),
+                        },
+                        &Uniform {
+                            name: "uWarpStrength",
+                            value: UniformValue::Float(warp_strength),
+                        },
+                    ]);
+                    &self.domain_warp_noise_pass
+                }
+            };
+
+            noise_pass.use_program();
 
             unsafe {
                 self.context.bind_vertex_array(Some(self.noise_buffer.id));
@@ -274,9 +448,7 @@ impl NoiseInjector {
         elapsed_time: f32,
     ) -> () {
         for channel in self.channels.iter_mut() {
-            let blend_progress: f32 = ((elapsed_time - channel.blend_begin_time)
-                / channel.noise.blend_duration)
-                .clamp(0.0, 1.0);
+            let blend_progress = channel.blend_progress(elapsed_time);
 
             if blend_progress >= 1.0 - 0.0001 {
                 continue;
@@ -322,7 +494,130 @@ impl NoiseInjector {
         }
     }
 
-    #[allow(dead_code)]
+    // Add `amount` of `value` to a quantity, like the dye or temperature,
+    // wherever the noise is pushing the fluid. This has to run before
+    // `blend_noise_into`, which moves the blend progress along.
+    pub fn blend_quantity_into(
+        &self,
+        textures: &DoubleFramebuffer,
+        value: [f32; 4],
+        amount: f32,
+        elapsed_time: f32,
+    ) {
+        for channel in self.channels.iter() {
+            let blend_progress = channel.blend_progress(elapsed_time);
+
+            if blend_progress >= 1.0 - 0.0001 {
+                continue;
+            }
+
+            let delta_blend_progress = blend_progress - channel.last_blend_progress;
+
+            textures.draw_to(&self.context, |quantity_texture| {
+                self.blend_quantity_pass.set_uniforms(&[
+                    &Uniform {
+                        name: "uBlendProgress",
+                        value: UniformValue::Float(delta_blend_progress),
+                    },
+                    &Uniform {
+                        name: "uAmount",
+                        value: UniformValue::Float(amount),
+                    },
+                    &Uniform {
+                        name: "uValue",
+                        value: UniformValue::Vec4Array(&value),
+                    },
+                ]);
+
+                unsafe {
+                    self.context.bind_vertex_array(Some(self.noise_buffer.id));
+
+                    self.context.bind_buffer_base(
+                        glow::UNIFORM_BUFFER,
+                        3,
+                        Some(channel.uniforms.id),
+                    );
+
+                    self.context.active_texture(glow::TEXTURE0);
+                    self.context
+                        .bind_texture(glow::TEXTURE_2D, Some(quantity_texture.texture));
+
+                    self.context.active_texture(glow::TEXTURE1);
+                    self.context
+                        .bind_texture(glow::TEXTURE_2D, Some(channel.texture.texture));
+
+                    self.context
+                        .draw_elements(glow::TRIANGLES, 6, glow::UNSIGNED_SHORT, 0);
+                }
+            });
+        }
+    }
+
+    pub fn snapshot(&self) -> Result<NoiseState, render::Problem> {
+        let mut channels = Vec::with_capacity(self.channels.len());
+
+        for channel in self.channels.iter() {
+            channels.push(NoiseChannelState {
+                blend_begin_time: channel.blend_begin_time,
+                last_blend_progress: channel.last_blend_progress,
+                offset1: channel.offset1,
+                offset2: channel.offset2,
+                texture: channel.texture.read_data()?,
+            });
+        }
+
+        Ok(NoiseState {
+            width: self.width,
+            height: self.height,
+            channels,
+        })
+    }
+
+    // Load a snapshot into the existing channels, in order. The snapshot has to
+    // have as many channels as the settings, or the noise would end up in the
+    // wrong channels.
+    // Check that a snapshot has a texture of the right size for every
+    // channel, without changing anything.
+    pub fn check_state(&self, state: &NoiseState) -> Result<(), render::Problem> {
+        if state.channels.len() != self.channels.len() {
+            return Err(render::Problem::WrongDataSize {
+                expected: self.channels.len(),
+                actual: state.channels.len(),
+            });
+        }
+
+        for (channel, channel_state) in self.channels.iter().zip(state.channels.iter()) {
+            channel.texture.check_resampled_f32_data(
+                state.width,
+                state.height,
+                &channel_state.texture,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    pub fn restore(&mut self, state: &NoiseState) -> Result<(), render::Problem> {
+        self.check_state(state)?;
+
+        for (channel, channel_state) in self.channels.iter_mut().zip(state.channels.iter()) {
+            channel.blend_begin_time = channel_state.blend_begin_time;
+            channel.last_blend_progress = channel_state.last_blend_progress;
+            channel.offset1 = channel_state.offset1;
+            channel.offset2 = channel_state.offset2;
+            channel.upload_offsets(&self.context);
+
+            channel.texture.write_resampled_f32_data(
+                &self.resampler,
+                state.width,
+                state.height,
+                &channel_state.texture,
+            )?;
+        }
+
+        Ok(())
+    }
+
     pub fn get_noise_channel(&self, channel_number: usize) -> Option<&Framebuffer> {
         self.channels
             .get(channel_number)
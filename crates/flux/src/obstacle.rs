@@ -0,0 +1,69 @@
+use crate::settings::Obstacle;
+
+// A bitmap of solid areas, for shapes that are easier to draw than to
+// describe, like logos. It has one byte per pixel, with rows from top to
+// bottom, and anything above 127 is solid. The image is stretched to cover the
+// whole fluid.
+#[derive(Clone, Debug)]
+pub struct ObstacleMask {
+    width: u32,
+    height: u32,
+    data: Vec<u8>,
+}
+
+impl ObstacleMask {
+    // Returns `None` if `data` doesn’t have exactly `width * height` bytes.
+    pub fn new(width: u32, height: u32, data: Vec<u8>) -> Option<Self> {
+        let size = width.checked_mul(height)?;
+        if size == 0 || data.len() != size as usize {
+            return None;
+        }
+
+        Some(Self {
+            width,
+            height,
+            data,
+        })
+    }
+
+    // Whether the pixel under a texture coordinate of the fluid is solid.
+    fn is_solid(&self, texture_coord: [f32; 2]) -> bool {
+        let x = (texture_coord[0] * self.width as f32) as u32;
+        let y = ((1.0 - texture_coord[1]) * self.height as f32) as u32;
+        let index = y.min(self.height - 1) * self.width + x.min(self.width - 1);
+
+        self.data[index as usize] > 127
+    }
+}
+
+// Draw the obstacles onto a grid the size of the fluid, with 1 in the cells
+// whose centers are solid and 0 everywhere else. The second channel is unused,
+// to match the other fluid textures.
+pub fn rasterize(
+    obstacles: &[Obstacle],
+    mask: Option<&ObstacleMask>,
+    width: u32,
+    height: u32,
+) -> Vec<[f32; 2]> {
+    let ratio = width as f32 / height as f32;
+    let mut output = Vec::with_capacity((width * height) as usize);
+
+    for y in 0..height {
+        for x in 0..width {
+            let texture_coord = [
+                (x as f32 + 0.5) / width as f32,
+                (y as f32 + 0.5) / height as f32,
+            ];
+            let point = [texture_coord[0] * ratio, texture_coord[1]];
+
+            let is_solid = mask.is_some_and(|mask| mask.is_solid(texture_coord))
+                || obstacles
+                    .iter()
+                    .any(|obstacle| obstacle.signed_distance(point, ratio) < 0.0);
+
+            output.push([if is_solid { 1.0 } else { 0.0 }, 0.0]);
+        }
+    }
+
+    output
+}
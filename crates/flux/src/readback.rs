@@ -0,0 +1,56 @@
+use crate::render;
+
+// A simulation field that can be read back with `Flux::read_field`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Field {
+    Velocity,
+    Pressure,
+    Divergence,
+    // The last noise generated by a noise channel, counting from 0
+    Noise(usize),
+}
+
+// The values of a field, `channels` floats per texel. Rows go from the bottom
+// of the screen to the top.
+#[derive(Clone, Debug)]
+pub struct FieldData {
+    pub width: u32,
+    pub height: u32,
+    pub channels: u32,
+    pub data: Vec<f32>,
+}
+
+impl FieldData {
+    // The value of the texel at `x`, `y`, counting from the bottom-left.
+    pub fn get(&self, x: u32, y: u32) -> &[f32] {
+        let channels = self.channels as usize;
+        let index = channels * (y * self.width + x) as usize;
+        &self.data[index..index + channels]
+    }
+}
+
+// A field on its way back from the GPU. Check `is_ready` once a frame, say, and
+// call `finish` when it returns true to get the data without stalling.
+pub struct FieldReadback {
+    pub(crate) read: render::PendingRead,
+}
+
+impl FieldReadback {
+    pub fn is_ready(&self) -> bool {
+        self.read.is_ready()
+    }
+
+    // Wait for the data, if it isn’t ready yet, and return it.
+    pub fn finish(self) -> FieldData {
+        let width = self.read.width;
+        let height = self.read.height;
+        let channels = self.read.channels as u32;
+
+        FieldData {
+            width,
+            height,
+            channels,
+            data: self.read.finish(),
+        }
+    }
+}
@@ -1,6 +1,10 @@
+use crate::data;
+
 use glow::HasContext;
 use rustc_hash::FxHashMap;
+use serde::Serialize;
 use std::cell::{Ref, RefCell};
+use std::fmt;
 use std::rc::Rc;
 use thiserror::Error;
 
@@ -31,6 +35,9 @@ pub enum Problem {
     })]
     CannotCreateShader(Option<String>),
 
+    #[error("Cannot find the shader include `{0}`")]
+    CannotFindShaderInclude(String),
+
     #[error("Cannot create program")]
     CannotCreateProgram,
 
@@ -48,9 +55,41 @@ pub enum Problem {
 
     #[error("Vertex attribute type is not supported")]
     CannotBindUnsupportedVertexType,
+
+    #[error("OpenGL {major}.{minor} is too old. Flux needs OpenGL 3.3 or later")]
+    UnsupportedGlVersion { major: u32, minor: u32 },
+}
+
+// Debug builds count the GL objects owned by the wrappers in this module, per
+// thread, so that leaks show up in tests. Each wrapper deletes its objects when
+// it’s dropped.
+#[cfg(debug_assertions)]
+thread_local! {
+    static LIVE_OBJECTS: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+}
+
+// The number of GL objects created on this thread that haven’t been deleted
+// yet. Only available in debug builds.
+#[cfg(debug_assertions)]
+pub fn live_gl_objects() -> usize {
+    LIVE_OBJECTS.with(|live| live.get())
+}
+
+pub fn track_created(count: usize) -> () {
+    #[cfg(debug_assertions)]
+    LIVE_OBJECTS.with(|live| live.set(live.get() + count));
+    #[cfg(not(debug_assertions))]
+    let _ = count;
+}
+
+pub fn track_deleted(count: usize) -> () {
+    #[cfg(debug_assertions)]
+    LIVE_OBJECTS.with(|live| live.set(live.get().saturating_sub(count)));
+    #[cfg(not(debug_assertions))]
+    let _ = count;
 }
 
-#[derive(Clone, Debug)]
+#[derive(Debug)]
 pub struct Buffer {
     context: Context,
     pub id: glow::Buffer,
@@ -80,6 +119,7 @@ impl Buffer {
 
             buffer
         };
+        track_created(1);
 
         Ok(Self {
             context: Rc::clone(context),
@@ -89,6 +129,40 @@ impl Buffer {
         })
     }
 
+    // Read the whole buffer back as floats. This stalls until the GPU has
+    // finished writing to it.
+    pub fn read_f32_data(&self) -> Vec<f32> {
+        let mut data = vec![0.0_f32; self.size];
+
+        unsafe {
+            self.context.bind_buffer(self.type_, Some(self.id));
+            self.context
+                .get_buffer_sub_data(self.type_, 0, bytemuck::cast_slice_mut(&mut data));
+            self.context.bind_buffer(self.type_, None);
+        }
+
+        data
+    }
+
+    // Overwrite the contents of the buffer. The size can’t change.
+    pub fn write_f32_data(&self, data: &[f32]) -> Result<()> {
+        if data.len() != self.size {
+            return Err(Problem::WrongDataSize {
+                expected: self.size,
+                actual: data.len(),
+            });
+        }
+
+        unsafe {
+            self.context.bind_buffer(self.type_, Some(self.id));
+            self.context
+                .buffer_sub_data_u8_slice(self.type_, 0, bytemuck::cast_slice(data));
+            self.context.bind_buffer(self.type_, None);
+        }
+
+        Ok(())
+    }
+
     pub fn from_u16(context: &Context, data: &[u16], buffer_type: u32, usage: u32) -> Result<Self> {
         // let memory_buffer = wasm_bindgen::memory()
         //     .dyn_into::<WebAssembly::Memory>()
@@ -109,6 +183,7 @@ impl Buffer {
 
             buffer
         };
+        track_created(1);
 
         Ok(Self {
             context: Rc::clone(context),
@@ -143,6 +218,7 @@ impl Buffer {
 
             buffer
         };
+        track_created(1);
 
         Ok(Self {
             context: Rc::clone(context),
@@ -153,6 +229,15 @@ impl Buffer {
     }
 }
 
+impl Drop for Buffer {
+    fn drop(&mut self) {
+        unsafe {
+            self.context.delete_buffer(self.id);
+        }
+        track_deleted(1);
+    }
+}
+
 #[derive(Clone, Copy)]
 pub struct TextureOptions {
     pub mag_filter: GlDataType,
@@ -160,6 +245,9 @@ pub struct TextureOptions {
     pub wrap_s: GlDataType,
     pub wrap_t: GlDataType,
     pub format: GlDataType,
+    // Whether an RGBA8 texture holds two floats packed into pairs of bytes,
+    // rather than four values between 0 and 1. See `FieldFormat::Packed8`.
+    pub packed: bool,
 }
 
 impl Default for TextureOptions {
@@ -170,11 +258,321 @@ impl Default for TextureOptions {
             wrap_s: glow::CLAMP_TO_EDGE,
             wrap_t: glow::CLAMP_TO_EDGE,
             format: glow::RGBA32F,
+            packed: false,
         }
     }
 }
 
-#[derive(Clone)]
+// What the context supports, probed once when Flux starts. This decides how
+// the simulation is stored and drawn, and is worth including in bug reports.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Capabilities {
+    // The version strings, as reported by the driver
+    pub gl_version: String,
+    pub glsl_version: String,
+    pub major_version: u32,
+    pub minor_version: u32,
+    // OpenGL ES or WebGL, rather than desktop OpenGL
+    pub is_embedded: bool,
+    pub vendor: String,
+    pub renderer: String,
+    pub max_texture_size: u32,
+    pub max_samples: u32,
+    pub supports_transform_feedback: bool,
+    pub can_render_to_float32: bool,
+    pub can_render_to_float16: bool,
+    pub can_filter_float32: bool,
+}
+
+impl Capabilities {
+    pub fn probe(context: &Context) -> Self {
+        let version = context.version();
+
+        let (gl_version, glsl_version, max_texture_size, max_samples) = unsafe {
+            (
+                context.get_parameter_string(glow::VERSION),
+                context.get_parameter_string(glow::SHADING_LANGUAGE_VERSION),
+                context.get_parameter_i32(glow::MAX_TEXTURE_SIZE).max(0) as u32,
+                context.get_parameter_i32(glow::MAX_SAMPLES).max(0) as u32,
+            )
+        };
+        let (vendor, renderer) = vendor_strings(context);
+
+        Self {
+            gl_version,
+            glsl_version,
+            major_version: version.major,
+            minor_version: version.minor,
+            is_embedded: version.is_embedded,
+            vendor,
+            renderer,
+            max_texture_size,
+            max_samples,
+            // Core in OpenGL 3.0 and OpenGL ES 3.0
+            supports_transform_feedback: version.major >= 3,
+            can_render_to_float32: can_render_to(context, glow::RG32F),
+            can_render_to_float16: can_render_to(context, glow::RG16F),
+            can_filter_float32: can_filter_float32(context),
+        }
+    }
+}
+
+impl fmt::Display for Capabilities {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let yes_no = |supported: bool| if supported { "yes" } else { "no" };
+
+        writeln!(f, "Vendor: {}", self.vendor)?;
+        writeln!(f, "Renderer: {}", self.renderer)?;
+        writeln!(f, "Version: {}", self.gl_version)?;
+        writeln!(f, "GLSL version: {}", self.glsl_version)?;
+        writeln!(f, "Max texture size: {}", self.max_texture_size)?;
+        writeln!(f, "Max samples: {}", self.max_samples)?;
+        writeln!(
+            f,
+            "Transform feedback: {}",
+            yes_no(self.supports_transform_feedback)
+        )?;
+        writeln!(
+            f,
+            "Render to 32-bit floats: {}",
+            yes_no(self.can_render_to_float32)
+        )?;
+        writeln!(
+            f,
+            "Render to 16-bit floats: {}",
+            yes_no(self.can_render_to_float16)
+        )?;
+        write!(
+            f,
+            "Filter 32-bit floats: {}",
+            yes_no(self.can_filter_float32)
+        )
+    }
+}
+
+// Browsers hide the real vendor and renderer behind `WEBGL_debug_renderer_info`.
+fn vendor_strings(context: &Context) -> (String, String) {
+    const UNMASKED_VENDOR_WEBGL: u32 = 0x9245;
+    const UNMASKED_RENDERER_WEBGL: u32 = 0x9246;
+
+    unsafe {
+        if context
+            .supported_extensions()
+            .contains("WEBGL_debug_renderer_info")
+        {
+            (
+                context.get_parameter_string(UNMASKED_VENDOR_WEBGL),
+                context.get_parameter_string(UNMASKED_RENDERER_WEBGL),
+            )
+        } else {
+            (
+                context.get_parameter_string(glow::VENDOR),
+                context.get_parameter_string(glow::RENDERER),
+            )
+        }
+    }
+}
+
+// How the simulation fields are stored. Rendering to 32-bit float textures
+// needs `EXT_color_buffer_float` on WebGL and OpenGL ES, which many mobile GPUs
+// lack, so there are two fallbacks: half floats, and packing each value into a
+// pair of bytes of an RGBA8 texture.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FieldFormat {
+    Float32,
+    Float16,
+    Packed8,
+}
+
+impl FieldFormat {
+    // Pick the most precise format that the context can both render to and
+    // filter.
+    pub fn detect(capabilities: &Capabilities) -> Self {
+        if capabilities.can_render_to_float32 && capabilities.can_filter_float32 {
+            FieldFormat::Float32
+        } else if capabilities.can_render_to_float16 {
+            FieldFormat::Float16
+        } else {
+            FieldFormat::Packed8
+        }
+    }
+
+    // Options for the two-channel fields the simulation renders to: velocity,
+    // pressure, noise and so on.
+    pub fn field_options(self) -> TextureOptions {
+        let (format, packed) = match self {
+            FieldFormat::Float32 => (glow::RG32F, false),
+            FieldFormat::Float16 => (glow::RG16F, false),
+            FieldFormat::Packed8 => (glow::RGBA8, true),
+        };
+
+        TextureOptions {
+            mag_filter: glow::LINEAR,
+            min_filter: glow::LINEAR,
+            format,
+            packed,
+            ..Default::default()
+        }
+    }
+
+    // Options for quantities carried along by the fluid, like dye. With 8-bit
+    // textures, they’re clamped between 0 and 1.
+    pub fn quantity_options(self, channels: usize) -> TextureOptions {
+        let format = match (self, channels) {
+            (FieldFormat::Float32, 2) => glow::RG32F,
+            (FieldFormat::Float32, _) => glow::RGBA32F,
+            (FieldFormat::Float16, 2) => glow::RG16F,
+            (FieldFormat::Float16, _) => glow::RGBA16F,
+            (FieldFormat::Packed8, 2) => glow::RG8,
+            (FieldFormat::Packed8, _) => glow::RGBA8,
+        };
+
+        TextureOptions {
+            mag_filter: glow::LINEAR,
+            min_filter: glow::LINEAR,
+            format,
+            ..Default::default()
+        }
+    }
+
+    // Options for two-channel textures that are only ever sampled. Any context
+    // can filter half floats, but not necessarily full ones.
+    pub fn sampled_options(self) -> TextureOptions {
+        let format = match self {
+            FieldFormat::Float32 => glow::RG32F,
+            _ => glow::RG16F,
+        };
+
+        TextureOptions {
+            mag_filter: glow::LINEAR,
+            min_filter: glow::LINEAR,
+            format,
+            ..Default::default()
+        }
+    }
+
+    // Prepend the definitions the shaders use to read and write fields.
+    //
+    // Shaders read fields with `readField`, which works like `texture`, and
+    // declare a field output with `FIELD_OUTPUT(name);` instead of
+    // `out vec2 name;`. When the fields are packed, `main` is wrapped so that
+    // the output is packed after it runs.
+    pub fn prepare_shader(self, source: &str) -> String {
+        let output = field_output_name(source);
+
+        let mut prepared = String::with_capacity(source.len() + 1024);
+
+        match self {
+            FieldFormat::Float32 | FieldFormat::Float16 => {
+                prepared.push_str("#define readField texture\n");
+                prepared.push_str("#define FIELD_OUTPUT(name) out vec2 name\n");
+                prepared.push_str(source);
+            }
+            FieldFormat::Packed8 => {
+                prepared.push_str(&format!("#define FIELD_RANGE {:.1}\n", PACKED_FIELD_RANGE));
+                prepared.push_str(PACKED_FIELD_FUNCTIONS);
+                prepared.push_str("#define FIELD_OUTPUT(name) vec2 name\n");
+
+                match output {
+                    Some(name) => {
+                        prepared.push_str("out vec4 packedField;\n");
+                        prepared.push_str("#define main fieldMain\n");
+                        prepared.push_str(source);
+                        prepared.push_str(&format!(
+                            "\n#undef main\nvoid main() {{\n  fieldMain();\n  packedField = packField({});\n}}\n",
+                            name
+                        ));
+                    }
+                    None => prepared.push_str(source),
+                }
+            }
+        }
+
+        prepared
+    }
+}
+
+// Packed fields store values between -PACKED_FIELD_RANGE and
+// PACKED_FIELD_RANGE as 16-bit fixed point. The simulation stays well within
+// ±1.5 at the default settings.
+const PACKED_FIELD_RANGE: f32 = 4.0;
+
+// Must match `encode_bytes` and `decode_pixels`. Packed textures have to be
+// unpacked before they’re filtered. Some GPUs filter 8-bit textures with only 8
+// bits of precision, which isn’t enough to unpack the filtered bytes. So, like
+// `FieldResampler`, `readField` filters the unpacked values of the four nearest
+// texels. It samples their centers, which filtering leaves as they are, so that
+// the wrap mode still applies.
+const PACKED_FIELD_FUNCTIONS: &str = "
+precision highp float;
+precision highp sampler2D;
+
+vec2 unpackField(vec4 texel) {
+  vec4 bytes = 255.0 * texel;
+  vec2 fixedPoint = 256.0 * bytes.xz + bytes.yw;
+  return FIELD_RANGE * (fixedPoint - 32768.0) / 32768.0;
+}
+
+vec4 readField(sampler2D field, vec2 coord) {
+  vec2 size = vec2(textureSize(field, 0));
+  vec2 position = coord * size - 0.5;
+  vec2 corner = floor(position);
+  vec2 weight = position - corner;
+
+  vec2 texelSize = 1.0 / size;
+  vec2 center = (corner + 0.5) * texelSize;
+  vec2 bottom = mix(
+    unpackField(texture(field, center)),
+    unpackField(texture(field, center + vec2(texelSize.x, 0.0))),
+    weight.x);
+  vec2 top = mix(
+    unpackField(texture(field, center + vec2(0.0, texelSize.y))),
+    unpackField(texture(field, center + texelSize)),
+    weight.x);
+  return vec4(mix(bottom, top, weight.y), 0.0, 1.0);
+}
+
+vec4 packField(vec2 value) {
+  vec2 fixedPoint = clamp(floor(value / FIELD_RANGE * 32768.0 + 32768.5), 0.0, 65535.0);
+  vec2 high = floor(fixedPoint / 256.0);
+  vec2 low = fixedPoint - 256.0 * high;
+  return vec4(high.x, low.x, high.y, low.y) / 255.0;
+}
+";
+
+fn field_output_name(source: &str) -> Option<&str> {
+    let start = source.find("FIELD_OUTPUT(")? + "FIELD_OUTPUT(".len();
+    let end = start + source[start..].find(')')?;
+    Some(source[start..end].trim())
+}
+
+fn can_render_to(context: &Context, format: GlDataType) -> bool {
+    let options = TextureOptions {
+        format,
+        ..Default::default()
+    };
+
+    let complete = Framebuffer::new(context, 1, 1, options)
+        .and_then(|framebuffer| framebuffer.with_data(None::<&[f32]>))
+        .is_ok_and(|framebuffer| framebuffer.is_complete());
+
+    // Clear the error an unsupported format might have raised.
+    unsafe { context.get_error() };
+
+    complete
+}
+
+// Desktop OpenGL can always filter float textures. OpenGL ES and WebGL need an
+// extension.
+fn can_filter_float32(context: &Context) -> bool {
+    let extensions = context.supported_extensions();
+
+    !context.version().is_embedded
+        || extensions.contains("OES_texture_float_linear")
+        || extensions.contains("GL_OES_texture_float_linear")
+}
+
 pub struct Framebuffer {
     context: Context,
     pub id: glow::Framebuffer,
@@ -228,12 +626,17 @@ impl Framebuffer {
             );
             context.bind_texture(glow::TEXTURE_2D, None);
 
-            let framebuffer = context
-                .create_framebuffer()
-                .map_err(|_| Problem::CannotCreateFramebuffer)?;
+            let framebuffer = match context.create_framebuffer() {
+                Ok(framebuffer) => framebuffer,
+                Err(_) => {
+                    context.delete_texture(texture);
+                    return Err(Problem::CannotCreateFramebuffer);
+                }
+            };
 
             (framebuffer, texture)
         };
+        track_created(2);
 
         Ok(Self {
             context: Rc::clone(context),
@@ -250,27 +653,43 @@ impl Framebuffer {
     }
 
     pub fn with_data<T: bytemuck::Pod>(self, data: Option<&[T]>) -> Result<Self> {
+        self.write_data(data)?;
+        Ok(self)
+    }
+
+    // Replace the contents of the texture. `None` leaves it uninitialized.
+    pub fn write_data<T: bytemuck::Pod>(&self, data: Option<&[T]>) -> Result<()> {
         let TextureFormat {
             internal_format,
             format,
             type_,
             size,
-        } = detect_texture_format(self.options.format)?;
+            packed,
+        } = detect_texture_format(&self.options)?;
 
-        let expected_size = size * ((self.width * self.height) as usize);
         if let Some(buffer) = data {
-            if buffer.len() != expected_size {
-                return Err(Problem::WrongDataSize {
-                    expected: expected_size,
-                    actual: buffer.len(),
-                });
-            }
+            check_data_size(size, self.width, self.height, buffer)?;
         }
 
+        // 8-bit textures take floats too, and encode them here
+        let bytes = match (data, type_) {
+            (Some(buffer), glow::UNSIGNED_BYTE) => {
+                let values: &[f32] =
+                    bytemuck::try_cast_slice(buffer).map_err(|_| Problem::CannotWriteToTexture)?;
+                Some(encode_bytes(values, packed))
+            }
+            _ => None,
+        };
+
         unsafe {
             self.context
                 .bind_texture(glow::TEXTURE_2D, Some(self.texture));
 
+            // Rows of 8-bit texels aren’t padded to 4 bytes
+            if type_ == glow::UNSIGNED_BYTE {
+                self.context.pixel_store_i32(glow::UNPACK_ALIGNMENT, 1);
+            }
+
             // let array = js_sys::Float32Array::view(data);
             self.context.tex_image_2d(
                 glow::TEXTURE_2D,
@@ -281,8 +700,12 @@ impl Framebuffer {
                 0,
                 format,
                 type_,
-                data.map(|buffer| bytemuck::cast_slice(buffer)),
+                match bytes {
+                    Some(ref bytes) => Some(bytes),
+                    None => data.map(|buffer| bytemuck::cast_slice(buffer)),
+                },
             );
+            self.context.pixel_store_i32(glow::UNPACK_ALIGNMENT, 4);
             // .map_err(|Err(Problem::CannotWriteToTexture))?;
 
             self.context.bind_texture(glow::TEXTURE_2D, None);
@@ -299,14 +722,49 @@ impl Framebuffer {
             self.context.bind_framebuffer(glow::FRAMEBUFFER, None);
         }
 
-        Ok(self)
+        Ok(())
+    }
+
+    // Like `write_data`, but for data that is `width` by `height`. If the size
+    // doesn’t match, the data is stretched to fit with `resampler`.
+    // Check that `data` holds a `width` by `height` grid in the format of this
+    // texture, without writing it.
+    pub fn check_resampled_f32_data(&self, width: u32, height: u32, data: &[f32]) -> Result<()> {
+        let TextureFormat { size, .. } = detect_texture_format(&self.options)?;
+        check_data_size(size, width, height, data)
+    }
+
+    pub fn write_resampled_f32_data(
+        &self,
+        resampler: &FieldResampler,
+        width: u32,
+        height: u32,
+        data: &[f32],
+    ) -> Result<()> {
+        if width == self.width && height == self.height {
+            return self.write_data(Some(data));
+        }
+
+        let source =
+            Framebuffer::new(&self.context, width, height, self.options)?.with_f32_data(data)?;
+        resampler.resample(&source, self);
+
+        Ok(())
     }
 
     pub fn zero_out(&self) -> Result<()> {
         self.clear_color_with(&[0.0, 0.0, 0.0, 0.0])
     }
 
+    // Packed textures are cleared to the first two channels of `color`.
     pub fn clear_color_with(&self, color: &[f32; 4]) -> Result<()> {
+        let color = if self.options.packed {
+            let bytes = encode_bytes(&color[..2], true);
+            [0, 1, 2, 3].map(|i| bytes[i] as f32 / 255.0)
+        } else {
+            *color
+        };
+
         unsafe {
             self.context
                 .bind_framebuffer(glow::FRAMEBUFFER, Some(self.id));
@@ -323,6 +781,138 @@ impl Framebuffer {
         Ok(())
     }
 
+    pub fn set_wrap(&mut self, wrap: GlDataType) -> () {
+        self.options.wrap_s = wrap;
+        self.options.wrap_t = wrap;
+
+        unsafe {
+            self.context
+                .bind_texture(glow::TEXTURE_2D, Some(self.texture));
+            self.context
+                .tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_WRAP_S, wrap as i32);
+            self.context
+                .tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_WRAP_T, wrap as i32);
+            self.context.bind_texture(glow::TEXTURE_2D, None);
+        }
+    }
+
+    // Read the texture back as RGBA floats, bottom row first. This stalls
+    // until the GPU has finished drawing to it. Packed textures come back with
+    // their two values in the first two channels.
+    pub fn read_f32_data(&self) -> Vec<f32> {
+        let texel_count = (self.width * self.height) as usize;
+        let is_8_bit = is_8_bit_format(self.options.format);
+        let mut data = vec![0_u8; if is_8_bit { 4 } else { 16 } * texel_count];
+
+        unsafe {
+            self.context
+                .bind_framebuffer(glow::READ_FRAMEBUFFER, Some(self.id));
+            self.context.read_pixels(
+                0,
+                0,
+                self.width as i32,
+                self.height as i32,
+                glow::RGBA,
+                if is_8_bit {
+                    glow::UNSIGNED_BYTE
+                } else {
+                    glow::FLOAT
+                },
+                glow::PixelPackData::Slice(&mut data),
+            );
+            self.context.bind_framebuffer(glow::READ_FRAMEBUFFER, None);
+        }
+
+        decode_pixels(&data, is_8_bit, self.options.packed)
+    }
+
+    // Read the texture back in its own format, with as many channels as it
+    // has. This is the same layout `with_data` takes.
+    pub fn read_data(&self) -> Result<Vec<f32>> {
+        let TextureFormat { size, .. } = detect_texture_format(&self.options)?;
+
+        Ok(pack_channels(&self.read_f32_data(), size))
+    }
+
+    // Like `read_data`, but without waiting on the GPU. The pixels are copied
+    // into a pixel buffer in the background. If that isn’t possible, this
+    // falls back to reading them right away.
+    pub fn read_data_async(&self) -> Result<PendingRead> {
+        let TextureFormat { size, .. } = detect_texture_format(&self.options)?;
+        let is_8_bit = is_8_bit_format(self.options.format);
+        let byte_count = if is_8_bit { 4 } else { 16 } * (self.width * self.height) as i32;
+
+        let transfer = unsafe {
+            self.context.create_buffer().ok().and_then(|buffer| {
+                self.context
+                    .bind_buffer(glow::PIXEL_PACK_BUFFER, Some(buffer));
+                self.context.buffer_data_size(
+                    glow::PIXEL_PACK_BUFFER,
+                    byte_count,
+                    glow::STREAM_READ,
+                );
+                self.context
+                    .bind_framebuffer(glow::READ_FRAMEBUFFER, Some(self.id));
+                self.context.read_pixels(
+                    0,
+                    0,
+                    self.width as i32,
+                    self.height as i32,
+                    glow::RGBA,
+                    if is_8_bit {
+                        glow::UNSIGNED_BYTE
+                    } else {
+                        glow::FLOAT
+                    },
+                    glow::PixelPackData::BufferOffset(0),
+                );
+                self.context.bind_framebuffer(glow::READ_FRAMEBUFFER, None);
+                self.context.bind_buffer(glow::PIXEL_PACK_BUFFER, None);
+
+                match self.context.fence_sync(glow::SYNC_GPU_COMMANDS_COMPLETE, 0) {
+                    Ok(fence) => {
+                        // Make sure the fence is sent off, or it might never
+                        // signal.
+                        self.context.flush();
+                        track_created(2);
+                        Some((buffer, fence))
+                    }
+                    Err(_) => {
+                        self.context.delete_buffer(buffer);
+                        None
+                    }
+                }
+            })
+        };
+
+        let data = match transfer {
+            Some(_) => None,
+            None => Some(self.read_f32_data()),
+        };
+
+        Ok(PendingRead {
+            context: Rc::clone(&self.context),
+            width: self.width,
+            height: self.height,
+            channels: size,
+            is_8_bit,
+            packed: self.options.packed,
+            transfer,
+            data,
+        })
+    }
+
+    pub fn is_complete(&self) -> bool {
+        unsafe {
+            self.context
+                .bind_framebuffer(glow::FRAMEBUFFER, Some(self.id));
+            let status = self.context.check_framebuffer_status(glow::FRAMEBUFFER);
+            self.context.bind_framebuffer(glow::FRAMEBUFFER, None);
+
+            status == glow::FRAMEBUFFER_COMPLETE
+        }
+    }
+
     pub fn draw_to<T>(&self, context: &Context, draw_call: T)
     where
         T: Fn() -> (),
@@ -335,6 +925,9 @@ impl Framebuffer {
         }
     }
 
+    // Stretch the texture over `target_framebuffer` with a linear blit. This
+    // filters each byte of packed textures separately, so use
+    // `FieldResampler` for those.
     pub fn blit_to(&self, context: &Context, target_framebuffer: &Framebuffer) {
         unsafe {
             context.disable(glow::BLEND);
@@ -358,6 +951,81 @@ impl Framebuffer {
     }
 }
 
+impl Drop for Framebuffer {
+    fn drop(&mut self) {
+        unsafe {
+            self.context.delete_framebuffer(self.id);
+            self.context.delete_texture(self.texture);
+        }
+        track_deleted(2);
+    }
+}
+
+// A read of a framebuffer started by `Framebuffer::read_data_async`.
+pub struct PendingRead {
+    context: Context,
+    pub width: u32,
+    pub height: u32,
+    // The number of floats per texel in the result
+    pub channels: usize,
+    // How the pixels are encoded, as in `Framebuffer::options`
+    is_8_bit: bool,
+    packed: bool,
+    // The pixel buffer being copied into, and the fence that signals once the
+    // copy is done
+    transfer: Option<(glow::Buffer, glow::Fence)>,
+    // The RGBA pixels, if they were read right away
+    data: Option<Vec<f32>>,
+}
+
+impl PendingRead {
+    // Whether `finish` can return without waiting on the GPU.
+    pub fn is_ready(&self) -> bool {
+        match self.transfer {
+            Some((_, fence)) => unsafe { self.context.get_sync_status(fence) == glow::SIGNALED },
+            None => true,
+        }
+    }
+
+    // Return the pixels, packed like `Framebuffer::read_data`. This waits for
+    // the copy to finish if it hasn’t yet.
+    pub fn finish(mut self) -> Vec<f32> {
+        let rgba = match self.transfer.take() {
+            Some((buffer, fence)) => unsafe {
+                let texel_size = if self.is_8_bit { 4 } else { 16 };
+                let mut data = vec![0_u8; texel_size * (self.width * self.height) as usize];
+
+                self.context
+                    .bind_buffer(glow::PIXEL_PACK_BUFFER, Some(buffer));
+                self.context
+                    .get_buffer_sub_data(glow::PIXEL_PACK_BUFFER, 0, &mut data);
+                self.context.bind_buffer(glow::PIXEL_PACK_BUFFER, None);
+
+                self.context.delete_sync(fence);
+                self.context.delete_buffer(buffer);
+                track_deleted(2);
+
+                decode_pixels(&data, self.is_8_bit, self.packed)
+            },
+            None => self.data.take().unwrap_or_default(),
+        };
+
+        pack_channels(&rgba, self.channels)
+    }
+}
+
+impl Drop for PendingRead {
+    fn drop(&mut self) {
+        if let Some((buffer, fence)) = self.transfer.take() {
+            unsafe {
+                self.context.delete_sync(fence);
+                self.context.delete_buffer(buffer);
+            }
+            track_deleted(2);
+        }
+    }
+}
+
 pub struct DoubleFramebuffer {
     pub width: u32,
     pub height: u32,
@@ -383,14 +1051,9 @@ impl DoubleFramebuffer {
     }
 
     pub fn with_data<T: bytemuck::Pod>(self, data: Option<&[T]>) -> Result<Self> {
-        // TODO: are these clones okay? The problem is that the builder pattern
-        // doesn’t work well with RefCell in the DoubleBuffer. Another option is
-        // to build with references and call a `finalize` method at the end.
-        self.front
-            .replace_with(|buffer| buffer.clone().with_data(data).unwrap());
+        self.front.borrow().write_data(data)?;
         // TODO: should we copy the data to the second buffer/texture, or just init with the right size?
-        self.back
-            .replace_with(|buffer| buffer.clone().with_data(data).unwrap());
+        self.back.borrow().write_data(data)?;
 
         Ok(self)
     }
@@ -411,6 +1074,11 @@ impl DoubleFramebuffer {
         Ok(())
     }
 
+    pub fn set_wrap(&self, wrap: GlDataType) -> () {
+        self.front.borrow_mut().set_wrap(wrap);
+        self.back.borrow_mut().set_wrap(wrap);
+    }
+
     pub fn current(&self) -> Ref<Framebuffer> {
         self.front.borrow()
     }
@@ -439,14 +1107,10 @@ impl DoubleFramebuffer {
         drop(framebuffer);
         self.swap();
     }
-
-    pub fn blit_to(&self, context: &Context, target_framebuffer: &DoubleFramebuffer) {
-        self.current()
-            .blit_to(context, &target_framebuffer.current());
-    }
 }
 
-#[derive(Clone)]
+// Each program owns its GL program, so passes that need different uniforms
+// should each compile their own, rather than share one.
 pub struct Program {
     context: Context,
     pub program: glow::Program,
@@ -455,30 +1119,61 @@ pub struct Program {
 }
 
 impl Program {
-    pub fn new(context: &Context, shaders: (&str, &str)) -> Result<Self> {
-        Self::new_impl(&context, shaders, None)
+    // Compile a program, with `defines` added to the top of both shaders as
+    // `#define NAME value`.
+    pub fn new(context: &Context, shaders: (&str, &str), defines: &[Define]) -> Result<Self> {
+        Self::new_impl(&context, shaders, defines, None)
+    }
+
+    // Compile a program that reads or writes fields stored in `field_format`.
+    pub fn new_for_fields(
+        context: &Context,
+        shaders: (&str, &str),
+        defines: &[Define],
+        field_format: FieldFormat,
+    ) -> Result<Self> {
+        let vertex_shader = field_format.prepare_shader(shaders.0);
+        let fragment_shader = field_format.prepare_shader(shaders.1);
+        Self::new_impl(&context, (&vertex_shader, &fragment_shader), defines, None)
     }
 
     pub fn new_with_transform_feedback(
         context: &Context,
         shaders: (&str, &str),
+        defines: &[Define],
         transform_feedback: &TransformFeedback,
     ) -> Result<Self> {
-        Self::new_impl(&context, shaders, Some(&transform_feedback))
+        Self::new_impl(&context, shaders, defines, Some(&transform_feedback))
     }
 
     pub fn new_impl(
         context: &Context,
         shaders: (&str, &str),
+        defines: &[Define],
         transform_feedback: Option<&TransformFeedback>,
     ) -> Result<Self> {
-        let vertex_shader = compile_shader(&context, glow::VERTEX_SHADER, shaders.0)?;
-        let fragment_shader = compile_shader(&context, glow::FRAGMENT_SHADER, shaders.1)?;
+        let vertex_source = preprocess_shader(shaders.0, defines)?;
+        let fragment_source = preprocess_shader(shaders.1, defines)?;
+
+        let vertex_shader = compile_shader(&context, glow::VERTEX_SHADER, &vertex_source)?;
+        let fragment_shader =
+            match compile_shader(&context, glow::FRAGMENT_SHADER, &fragment_source) {
+                Ok(shader) => shader,
+                Err(problem) => {
+                    unsafe { context.delete_shader(vertex_shader) };
+                    return Err(problem);
+                }
+            };
 
         let program = unsafe {
-            let program = context
-                .create_program()
-                .map_err(|_| Problem::CannotCreateProgram)?;
+            let program = match context.create_program() {
+                Ok(program) => program,
+                Err(_) => {
+                    context.delete_shader(vertex_shader);
+                    context.delete_shader(fragment_shader);
+                    return Err(Problem::CannotCreateProgram);
+                }
+            };
             context.attach_shader(program, vertex_shader);
             context.attach_shader(program, fragment_shader);
 
@@ -488,20 +1183,21 @@ impl Program {
 
             context.link_program(program);
 
-            if !context.get_program_link_status(program) {
-                return Err(Problem::CannotLinkProgram(
-                    context.get_program_info_log(program),
-                ));
-            }
-
             // Delete the shaders to free up memory
             context.detach_shader(program, vertex_shader);
             context.detach_shader(program, fragment_shader);
             context.delete_shader(vertex_shader);
             context.delete_shader(fragment_shader);
 
+            if !context.get_program_link_status(program) {
+                let log = context.get_program_info_log(program);
+                context.delete_program(program);
+                return Err(Problem::CannotLinkProgram(log));
+            }
+
             program
         };
+        track_created(1);
 
         // Get attribute locations
         let mut attributes = FxHashMap::default();
@@ -639,6 +1335,15 @@ impl Program {
     }
 }
 
+impl Drop for Program {
+    fn drop(&mut self) {
+        unsafe {
+            self.context.delete_program(self.program);
+        }
+        track_deleted(1);
+    }
+}
+
 #[derive(Clone)]
 struct AttributeInfo {
     type_: u32,
@@ -660,6 +1365,10 @@ pub struct Attribute {
     pub divisor: u32,
 }
 
+// A `#define` passed to a shader from Rust, so that constants shared with the
+// shaders live in one place.
+pub type Define<'a> = (&'a str, String);
+
 pub struct TransformFeedback<'a> {
     pub names: &'a [&'static str],
     pub mode: u32,
@@ -686,21 +1395,133 @@ pub enum UniformValue<'a> {
 }
 
 pub fn compile_shader(context: &Context, shader_type: u32, source: &str) -> Result<glow::Shader> {
+    let source = add_version_header(context, source)?;
+
     unsafe {
         let shader = context
             .create_shader(shader_type)
             .map_err(|_| Problem::CannotCreateShader(None))?;
-        context.shader_source(shader, source);
+        context.shader_source(shader, &source);
         context.compile_shader(shader);
 
         if context.get_shader_compile_status(shader) {
             Ok(shader)
         } else {
-            Err(Problem::CannotCreateShader(Some(
-                context.get_shader_info_log(shader),
-            )))
+            let log = context.get_shader_info_log(shader);
+            context.delete_shader(shader);
+            Err(Problem::CannotCreateShader(Some(log)))
+        }
+    }
+}
+
+static RESAMPLE_VERT_SHADER: &str = include_str!("../shaders/resample.vert");
+static RESAMPLE_FRAG_SHADER: &str = include_str!("../shaders/resample.frag");
+
+// Snippets shared between shaders, like the uniform blocks. Shaders pull
+// them in with `#include "common/…"`.
+static SHADER_INCLUDES: [(&str, &str); 8] = [
+    (
+        "common/fluid_uniforms.glsl",
+        include_str!("../shaders/common/fluid_uniforms.glsl"),
+    ),
+    (
+        "common/noise_uniforms.glsl",
+        include_str!("../shaders/common/noise_uniforms.glsl"),
+    ),
+    (
+        "common/projection.glsl",
+        include_str!("../shaders/common/projection.glsl"),
+    ),
+    (
+        "common/line_uniforms.glsl",
+        include_str!("../shaders/common/line_uniforms.glsl"),
+    ),
+    (
+        "common/simplex.glsl",
+        include_str!("../shaders/common/simplex.glsl"),
+    ),
+    (
+        "common/hash.glsl",
+        include_str!("../shaders/common/hash.glsl"),
+    ),
+    (
+        "common/obstacles.glsl",
+        include_str!("../shaders/common/obstacles.glsl"),
+    ),
+    (
+        "common/clamp_to_neighbours.glsl",
+        include_str!("../shaders/common/clamp_to_neighbours.glsl"),
+    ),
+];
+
+// Add the defines to the top of the shader and replace each `#include` line
+// with the snippet it names. GLSL has no `#include` of its own.
+fn preprocess_shader(source: &str, defines: &[Define]) -> Result<String> {
+    let mut prepared = String::with_capacity(source.len() + 1024);
+
+    for (name, value) in defines {
+        prepared.push_str(&format!("#define {} {}\n", name, value));
+    }
+    include_snippets(source, &mut prepared)?;
+
+    Ok(prepared)
+}
+
+fn include_snippets(source: &str, prepared: &mut String) -> Result<()> {
+    for line in source.lines() {
+        match line.trim().strip_prefix("#include") {
+            Some(path) => {
+                let path = path.trim().trim_matches('"');
+                let snippet = SHADER_INCLUDES
+                    .iter()
+                    .find(|(name, _)| *name == path)
+                    .map(|(_, snippet)| *snippet)
+                    .ok_or_else(|| Problem::CannotFindShaderInclude(path.to_string()))?;
+                include_snippets(snippet, prepared)?;
+            }
+            None => {
+                prepared.push_str(line);
+                prepared.push('\n');
+            }
         }
     }
+
+    Ok(())
+}
+
+// The shaders are written without a `#version` line, so that the same build
+// runs on OpenGL 3.3 and up, OpenGL ES 3 and WebGL 2. Pick one that matches
+// this context, or fail on older versions of desktop OpenGL.
+//
+// Precision qualifiers only mean something on OpenGL ES. Desktop OpenGL
+// ignores them, but versions before 4.0 reject default precision statements
+// for samplers, so those statements are dropped there.
+fn add_version_header(context: &Context, source: &str) -> Result<String> {
+    let version = context.version();
+    let mut prepared = String::with_capacity(source.len() + 32);
+
+    if version.is_embedded {
+        prepared.push_str("#version 300 es\n");
+        prepared.push_str(source);
+    } else {
+        let glsl_version = 100 * version.major + 10 * version.minor;
+        if glsl_version < 330 {
+            return Err(Problem::UnsupportedGlVersion {
+                major: version.major,
+                minor: version.minor,
+            });
+        }
+        prepared.push_str(&format!("#version {} core\n", glsl_version));
+
+        for line in source.lines() {
+            if !line.trim_start().starts_with("precision ") {
+                prepared.push_str(line);
+            }
+            prepared.push('\n');
+        }
+    }
+
+    Ok(prepared)
 }
 
 #[derive(Default)]
@@ -723,8 +1544,9 @@ pub struct MsaaPass {
 }
 
 impl MsaaPass {
-    pub fn new(context: &Context, width: u32, height: u32, requested_samples: u32) -> Result<Self> {
-        let (framebuffer, renderbuffer, samples) = unsafe {
+    // `samples` must be at most `Capabilities::max_samples`.
+    pub fn new(context: &Context, width: u32, height: u32, samples: u32) -> Result<Self> {
+        let (framebuffer, renderbuffer) = unsafe {
             let framebuffer = context
                 .create_framebuffer()
                 .map_err(|_| Problem::CannotCreateFramebuffer)?;
@@ -734,9 +1556,6 @@ impl MsaaPass {
             context.bind_framebuffer(glow::FRAMEBUFFER, Some(framebuffer));
             context.bind_renderbuffer(glow::RENDERBUFFER, Some(renderbuffer));
 
-            let max_samples = context.get_parameter_i32(glow::MAX_SAMPLES) as u32;
-            let samples = u32::min(requested_samples, max_samples);
-
             context.renderbuffer_storage_multisample(
                 glow::RENDERBUFFER,
                 samples as i32,
@@ -753,8 +1572,9 @@ impl MsaaPass {
             context.bind_framebuffer(glow::FRAMEBUFFER, None);
             context.bind_renderbuffer(glow::RENDERBUFFER, None);
 
-            (framebuffer, renderbuffer, samples)
+            (framebuffer, renderbuffer)
         };
+        track_created(2);
 
         Ok(MsaaPass {
             context: Rc::clone(context),
@@ -820,39 +1640,258 @@ impl MsaaPass {
     }
 }
 
+impl Drop for MsaaPass {
+    fn drop(&mut self) {
+        unsafe {
+            self.context.delete_framebuffer(self.framebuffer);
+            self.context.delete_renderbuffer(self.renderbuffer);
+        }
+        track_deleted(2);
+    }
+}
+
+// Stretches one texture over another, like `Framebuffer::blit_to`, but also
+// works for packed fields. A linear blit filters and rounds the high and low
+// bytes of packed values separately, which corrupts them, so those are drawn
+// with a shader that filters the unpacked values instead.
+pub struct FieldResampler {
+    context: Context,
+    // Only built for packed fields, which are the only ones that need it.
+    pass: Option<(Program, VertexArrayObject)>,
+}
+
+impl FieldResampler {
+    pub fn new(context: &Context, field_format: FieldFormat) -> Result<Self> {
+        let pass = match field_format {
+            FieldFormat::Packed8 => Some(Self::build_pass(context)?),
+            FieldFormat::Float32 | FieldFormat::Float16 => None,
+        };
+
+        Ok(Self {
+            context: Rc::clone(context),
+            pass,
+        })
+    }
+
+    fn build_pass(context: &Context) -> Result<(Program, VertexArrayObject)> {
+        let plane_vertices = Buffer::from_f32(
+            context,
+            &data::PLANE_VERTICES,
+            glow::ARRAY_BUFFER,
+            glow::STATIC_DRAW,
+        )?;
+        let plane_indices = Buffer::from_u16(
+            context,
+            &data::PLANE_INDICES,
+            glow::ELEMENT_ARRAY_BUFFER,
+            glow::STATIC_DRAW,
+        )?;
+
+        let program = Program::new_for_fields(
+            context,
+            (RESAMPLE_VERT_SHADER, RESAMPLE_FRAG_SHADER),
+            &[],
+            FieldFormat::Packed8,
+        )?;
+        let vertex_buffer = VertexArrayObject::new(
+            context,
+            &program,
+            &[(
+                &plane_vertices,
+                VertexBufferLayout {
+                    name: "position",
+                    size: 3,
+                    type_: glow::FLOAT,
+                    ..Default::default()
+                },
+            )],
+            Some(&plane_indices),
+        )?;
+
+        Ok((program, vertex_buffer))
+    }
+
+    // This binds its own vertex array and program, so callers that rely on
+    // theirs staying bound have to bind them again.
+    pub fn resample(&self, source: &Framebuffer, target: &Framebuffer) {
+        let (program, vertex_buffer) = match &self.pass {
+            Some(pass) if source.options.packed => pass,
+            _ => {
+                source.blit_to(&self.context, target);
+                return;
+            }
+        };
+
+        program.use_program();
+        target.draw_to(&self.context, || unsafe {
+            self.context.disable(glow::BLEND);
+            self.context.bind_vertex_array(Some(vertex_buffer.id));
+            self.context.active_texture(glow::TEXTURE0);
+            self.context
+                .bind_texture(glow::TEXTURE_2D, Some(source.texture));
+
+            self.context
+                .draw_elements(glow::TRIANGLES, 6, glow::UNSIGNED_SHORT, 0);
+        });
+    }
+
+    pub fn resample_double(&self, source: &DoubleFramebuffer, target: &DoubleFramebuffer) {
+        self.resample(&source.current(), &target.current());
+    }
+}
+
+// Data for a `width` by `height` texture has `channels` floats per texel.
+fn check_data_size<T>(channels: usize, width: u32, height: u32, data: &[T]) -> Result<()> {
+    let expected_size = channels * (width as usize) * (height as usize);
+    if data.len() != expected_size {
+        return Err(Problem::WrongDataSize {
+            expected: expected_size,
+            actual: data.len(),
+        });
+    }
+
+    Ok(())
+}
+
+// Drop the unused channels from RGBA pixels.
+fn pack_channels(rgba: &[f32], channels: usize) -> Vec<f32> {
+    rgba.chunks_exact(4)
+        .flat_map(|pixel| pixel[..channels].to_vec())
+        .collect()
+}
+
+// Encode floats for an 8-bit texture. Packed values become pairs of bytes, high
+// byte first. Anything else is clamped between 0 and 1.
+fn encode_bytes(values: &[f32], packed: bool) -> Vec<u8> {
+    if packed {
+        values
+            .iter()
+            .flat_map(|value| {
+                let fixed = (value / PACKED_FIELD_RANGE * 32768.0 + 32768.0)
+                    .round()
+                    .clamp(0.0, 65535.0) as u16;
+                fixed.to_be_bytes()
+            })
+            .collect()
+    } else {
+        values
+            .iter()
+            .map(|value| (value.clamp(0.0, 1.0) * 255.0).round() as u8)
+            .collect()
+    }
+}
+
+// Turn pixels read as RGBA into RGBA floats, undoing `encode_bytes`.
+fn decode_pixels(data: &[u8], is_8_bit: bool, packed: bool) -> Vec<f32> {
+    if !is_8_bit {
+        return data
+            .chunks_exact(4)
+            .map(|bytes| f32::from_ne_bytes(bytes.try_into().unwrap()))
+            .collect();
+    }
+
+    if packed {
+        data.chunks_exact(4)
+            .flat_map(|pixel| {
+                let decode = |high: u8, low: u8| {
+                    (u16::from_be_bytes([high, low]) as f32 - 32768.0) / 32768.0
+                        * PACKED_FIELD_RANGE
+                };
+                [
+                    decode(pixel[0], pixel[1]),
+                    decode(pixel[2], pixel[3]),
+                    0.0,
+                    1.0,
+                ]
+            })
+            .collect()
+    } else {
+        data.iter().map(|byte| *byte as f32 / 255.0).collect()
+    }
+}
+
+fn is_8_bit_format(internal_format: GlDataType) -> bool {
+    matches!(internal_format, glow::RG8 | glow::RGBA8)
+}
+
 struct TextureFormat {
     internal_format: GlDataType,
     format: GlDataType,
     type_: GlDataType,
     size: usize,
+    packed: bool,
 }
 
 // https://www.khronos.org/registry/webgl/specs/latest/2.0/#TEXTURE_TYPES_FORMATS_FROM_DOM_ELEMENTS_TABLE
-fn detect_texture_format(internal_format: GlDataType) -> Result<TextureFormat> {
+//
+// Half float textures are written from 32-bit floats too.
+fn detect_texture_format(options: &TextureOptions) -> Result<TextureFormat> {
+    let internal_format = options.format;
+    let packed = false;
+
     match internal_format {
         glow::R32F => Ok(TextureFormat {
             internal_format,
             format: glow::RED,
             type_: glow::FLOAT,
             size: 1,
+            packed,
         }),
         glow::RG32F => Ok(TextureFormat {
             internal_format,
             format: glow::RG,
             type_: glow::FLOAT,
             size: 2,
+            packed,
         }),
         glow::RGB32F => Ok(TextureFormat {
             internal_format,
             format: glow::RGB,
             type_: glow::FLOAT,
             size: 3,
+            packed,
         }),
         glow::RGBA32F => Ok(TextureFormat {
             internal_format,
             format: glow::RGBA,
             type_: glow::FLOAT,
             size: 4,
+            packed,
+        }),
+        glow::R16F => Ok(TextureFormat {
+            internal_format,
+            format: glow::RED,
+            type_: glow::FLOAT,
+            size: 1,
+            packed,
+        }),
+        glow::RG16F => Ok(TextureFormat {
+            internal_format,
+            format: glow::RG,
+            type_: glow::FLOAT,
+            size: 2,
+            packed,
+        }),
+        glow::RGBA16F => Ok(TextureFormat {
+            internal_format,
+            format: glow::RGBA,
+            type_: glow::FLOAT,
+            size: 4,
+            packed,
+        }),
+        glow::RG8 => Ok(TextureFormat {
+            internal_format,
+            format: glow::RG,
+            type_: glow::UNSIGNED_BYTE,
+            size: 2,
+            packed,
+        }),
+        glow::RGBA8 => Ok(TextureFormat {
+            internal_format,
+            format: glow::RGBA,
+            type_: glow::UNSIGNED_BYTE,
+            size: if options.packed { 2 } else { 4 },
+            packed: options.packed,
         }),
         _ => Err(Problem::UnsupportedTextureFormat),
     }
@@ -870,6 +1909,7 @@ impl VertexArrayObject {
                 .create_vertex_array()
                 .map_err(|_| Problem::OutOfMemory)?
         };
+        track_created(1);
 
         Ok(Self {
             id,
@@ -925,6 +1965,15 @@ impl VertexArrayObject {
     }
 }
 
+impl Drop for VertexArrayObject {
+    fn drop(&mut self) {
+        unsafe {
+            self.context.delete_vertex_array(self.id);
+        }
+        track_deleted(1);
+    }
+}
+
 pub fn bind_attributes(
     context: &Context,
     program: &Program,
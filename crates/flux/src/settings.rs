@@ -1,4 +1,4 @@
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 #[derive(Debug, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -10,8 +10,27 @@ pub struct Settings {
     pub fluid_simulation_frame_rate: f32,
     pub diffusion_iterations: u32,
     pub pressure_iterations: u32,
+    #[serde(default)]
+    pub advection_scheme: AdvectionScheme,
+    #[serde(default)]
+    pub pressure_solver: PressureSolver,
+    // Start each pressure solve from the last one instead of from
+    // `starting_pressure`.
+    #[serde(default)]
+    pub warm_start_pressure: bool,
+    // Stop the pressure solve early once the root mean square of the residual
+    // drops below this. Zero always runs every iteration.
+    #[serde(default)]
+    pub pressure_tolerance: f32,
+    #[serde(default)]
+    pub boundary: Boundary,
+    // Restores small eddies lost to numerical dissipation. Zero turns it off.
+    #[serde(default)]
+    pub vorticity_strength: f32,
 
     pub color_scheme: ColorScheme,
+    #[serde(default)]
+    pub color_interpolation: ColorInterpolation,
 
     pub line_length: f32,
     pub line_width: f32,
@@ -30,8 +49,256 @@ pub struct Settings {
     pub grid_spacing: u32,
     pub view_scale: f32,
 
-    pub noise_channel_1: Noise,
-    pub noise_channel_2: Noise,
+    // How far, in world space, and how strongly mouse and touch input pushes
+    // the fluid around.
+    #[serde(default = "default_pointer_radius")]
+    pub pointer_radius: f32,
+    #[serde(default = "default_pointer_strength")]
+    pub pointer_strength: f32,
+
+    // Solid shapes the fluid flows around. Lines inside them are hidden.
+    #[serde(default)]
+    pub obstacles: Vec<Obstacle>,
+
+    // A smoky layer of dye, carried along by the fluid and drawn under the
+    // lines. Leave it out to turn it off.
+    #[serde(default)]
+    pub dye: Option<Dye>,
+
+    // Heat, carried along by the fluid. Hot fluid rises and cold fluid sinks,
+    // so splats turn into plumes. Leave it out to turn it off.
+    #[serde(default)]
+    pub temperature: Option<Temperature>,
+
+    // Older settings have exactly two channels, stored as `noiseChannel1` and
+    // `noiseChannel2`. These are still accepted and read in that order.
+    // Settings with neither are rejected, rather than silently loaded without
+    // noise. Use an empty list to turn the noise off.
+    #[serde(
+        flatten,
+        deserialize_with = "deserialize_noise_channels",
+        serialize_with = "serialize_noise_channels"
+    )]
+    pub noise_channels: Vec<Noise>,
+}
+
+fn default_pointer_radius() -> f32 {
+    80.0
+}
+
+fn default_pointer_strength() -> f32 {
+    0.4
+}
+
+// How the fluid behaves at the edges of the screen.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub enum Boundary {
+    // The fluid slides along the edges, but can’t cross them.
+    #[default]
+    FreeSlip,
+    // The fluid sticks to the edges.
+    NoSlip,
+    // Fluid leaving one edge comes back in through the opposite one, so the
+    // flow tiles seamlessly. The noise channels don’t tile, though, so turn
+    // them down for perfectly seamless loops.
+    Periodic,
+    // The fluid flows out freely, and is drawn back in where the pressure is
+    // low.
+    Open,
+}
+
+impl Boundary {
+    // The value of `boundary` in the `FluidUniforms` block.
+    pub fn to_uniform(&self) -> i32 {
+        match self {
+            Boundary::FreeSlip => 0,
+            Boundary::NoSlip => 1,
+            Boundary::Periodic => 2,
+            Boundary::Open => 3,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Dye {
+    pub color: Color,
+    // How quickly the dye fades away, like `velocity_dissipation`.
+    pub dissipation: f32,
+    // How much dye the noise channels add as they push the fluid around.
+    pub noise_amount: f32,
+    // How much dye force splats and pointer input add, per unit of force.
+    pub splat_amount: f32,
+    pub opacity: f32,
+    #[serde(default)]
+    pub blend_mode: DyeBlendMode,
+}
+
+// How the lines are combined with the dye under them.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub enum DyeBlendMode {
+    Add,
+    #[default]
+    Screen,
+    // Keep the brighter of the two
+    Lighten,
+}
+
+impl DyeBlendMode {
+    // The value of `uBlendMode` in `endpoint.vert`.
+    pub fn to_uniform(&self) -> i32 {
+        match self {
+            DyeBlendMode::Add => 0,
+            DyeBlendMode::Screen => 1,
+            DyeBlendMode::Lighten => 2,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Temperature {
+    // How quickly the fluid cools down, or warms up, to the surroundings.
+    pub dissipation: f32,
+    // The upward acceleration per unit of temperature. Make it negative to
+    // have hot fluid sink instead.
+    pub buoyancy: f32,
+    // How much heat the noise channels add as they push the fluid around.
+    pub noise_amount: f32,
+    // How much heat force splats and pointer input add, per unit of force.
+    pub splat_amount: f32,
+}
+
+// How `Fluid::advect` moves the velocity along itself.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub enum AdvectionScheme {
+    // A single step back along the velocity. Fast, but it blurs the flow.
+    #[default]
+    SemiLagrangian,
+    // A semi-Lagrangian step, corrected by half the error of tracing it back
+    // again. Keeps much sharper detail for two extra passes.
+    MacCormack,
+    // Back and Forth Error Compensation and Correction. Like MacCormack, but
+    // corrects the velocity before a final semi-Lagrangian step. One pass
+    // more than MacCormack.
+    Bfecc,
+}
+
+// How `Fluid::solve_pressure` solves for the pressure.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub enum PressureSolver {
+    // `pressure_iterations` Jacobi iterations on the full grid.
+    #[default]
+    Jacobi,
+    // `cycles` multigrid V-cycles, with `smoothing_iterations` iterations on
+    // each level on the way down and again on the way back up.
+    #[serde(rename_all = "camelCase")]
+    Multigrid {
+        cycles: u32,
+        smoothing_iterations: u32,
+    },
+}
+
+// A solid shape inside the fluid.
+//
+// Positions are fractions of the fluid width and height, measured from the
+// bottom-left corner, so obstacles stay put relative to the edges of the
+// screen. Sizes are fractions of the fluid height, so shapes keep their
+// proportions.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub enum Obstacle {
+    #[serde(rename_all = "camelCase")]
+    Circle { center: [f32; 2], radius: f32 },
+    #[serde(rename_all = "camelCase")]
+    Rectangle {
+        center: [f32; 2],
+        size: [f32; 2],
+        #[serde(default)]
+        corner_radius: f32,
+    },
+    // Closed outlines, like the flattened contours of text glyphs. The points
+    // are offsets from `position`. Overlapping contours cut holes, following
+    // the even-odd rule.
+    #[serde(rename_all = "camelCase")]
+    Outline {
+        position: [f32; 2],
+        contours: Vec<Vec<[f32; 2]>>,
+    },
+}
+
+impl Obstacle {
+    // The signed distance from `point` to the edge of the obstacle, negative
+    // inside. Both are in fractions of the fluid height, with `ratio` the
+    // width of the fluid over its height.
+    pub fn signed_distance(&self, point: [f32; 2], ratio: f32) -> f32 {
+        let to_height_units = |position: [f32; 2]| [position[0] * ratio, position[1]];
+
+        match self {
+            Obstacle::Circle { center, radius } => {
+                let center = to_height_units(*center);
+                length([point[0] - center[0], point[1] - center[1]]) - radius
+            }
+
+            Obstacle::Rectangle {
+                center,
+                size,
+                corner_radius,
+            } => {
+                let center = to_height_units(*center);
+                let corner_radius = corner_radius.min(0.5 * size[0].min(size[1]));
+                let q = [
+                    (point[0] - center[0]).abs() - 0.5 * size[0] + corner_radius,
+                    (point[1] - center[1]).abs() - 0.5 * size[1] + corner_radius,
+                ];
+                length([q[0].max(0.0), q[1].max(0.0)]) + q[0].max(q[1]).min(0.0) - corner_radius
+            }
+
+            Obstacle::Outline { position, contours } => {
+                let position = to_height_units(*position);
+                let point = [point[0] - position[0], point[1] - position[1]];
+                let mut distance = f32::INFINITY;
+                let mut inside = false;
+
+                for contour in contours {
+                    let edges = contour.iter().zip(contour.iter().cycle().skip(1));
+
+                    for (a, b) in edges {
+                        let edge = [b[0] - a[0], b[1] - a[1]];
+                        let to_point = [point[0] - a[0], point[1] - a[1]];
+                        let edge_length = edge[0] * edge[0] + edge[1] * edge[1];
+                        let t = if edge_length > 0.0 {
+                            ((to_point[0] * edge[0] + to_point[1] * edge[1]) / edge_length)
+                                .clamp(0.0, 1.0)
+                        } else {
+                            0.0
+                        };
+                        distance = distance.min(length([
+                            to_point[0] - t * edge[0],
+                            to_point[1] - t * edge[1],
+                        ]));
+
+                        // Count the edges crossed by a ray going right.
+                        if (a[1] > point[1]) != (b[1] > point[1]) {
+                            let crossing = a[0] + (point[1] - a[1]) / (b[1] - a[1]) * edge[0];
+                            if point[0] < crossing {
+                                inside = !inside;
+                            }
+                        }
+                    }
+                }
+
+                if inside {
+                    -distance
+                } else {
+                    distance
+                }
+            }
+        }
+    }
+}
+
+fn length(vector: [f32; 2]) -> f32 {
+    (vector[0] * vector[0] + vector[1] * vector[1]).sqrt()
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -40,6 +307,119 @@ pub enum ColorScheme {
     Peacock,
     Poolside,
     Pollen,
+    Custom(#[serde(deserialize_with = "deserialize_color_stops")] Vec<Color>),
+}
+
+// The color space used to blend between neighbouring colors on the color wheel.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub enum ColorInterpolation {
+    #[default]
+    Srgb,
+    LinearRgb,
+    Oklab,
+    Oklch,
+}
+
+impl ColorInterpolation {
+    // The value of the `uColorInterpolation` uniform in `place_lines.vert`.
+    pub fn to_uniform(&self) -> i32 {
+        match self {
+            ColorInterpolation::Srgb => 0,
+            ColorInterpolation::LinearRgb => 1,
+            ColorInterpolation::Oklab => 2,
+            ColorInterpolation::Oklch => 3,
+        }
+    }
+}
+
+// The size of the color wheel array in `place_lines.vert`.
+pub const MAX_COLOR_STOPS: usize = 64;
+
+// An sRGB color. In JSON, this can be either a hex string, like `"#f3ce39"`,
+// or an array of components between 0 and 1, like `[0.95, 0.81, 0.22]`.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(try_from = "ColorValue", into = "String")]
+pub struct Color {
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ColorValue {
+    Hex(String),
+    Rgb([f32; 3]),
+}
+
+impl TryFrom<ColorValue> for Color {
+    type Error = String;
+
+    fn try_from(value: ColorValue) -> Result<Self, Self::Error> {
+        match value {
+            ColorValue::Hex(hex) => Color::from_hex(&hex),
+            ColorValue::Rgb([r, g, b]) => {
+                if [r, g, b].iter().all(|c| (0.0..=1.0).contains(c)) {
+                    Ok(Color { r, g, b })
+                } else {
+                    Err(format!(
+                        "Color components must be between 0 and 1: [{}, {}, {}]",
+                        r, g, b
+                    ))
+                }
+            }
+        }
+    }
+}
+
+impl From<Color> for String {
+    fn from(color: Color) -> Self {
+        let [r, g, b] = [color.r, color.g, color.b].map(|c| (c * 255.0).round() as u8);
+        format!("#{:02x}{:02x}{:02x}", r, g, b)
+    }
+}
+
+impl Color {
+    // Parse `#rgb` or `#rrggbb`. The leading `#` is optional.
+    pub fn from_hex(hex: &str) -> Result<Self, String> {
+        let digits = hex.trim_start_matches('#');
+        let invalid = || format!("Invalid hex color: {}", hex);
+
+        let channels = match digits.len() {
+            3 => digits
+                .chars()
+                .map(|c| c.to_digit(16).map(|d| (d * 17) as u8))
+                .collect::<Option<Vec<u8>>>(),
+            6 => (0..6)
+                .step_by(2)
+                .map(|i| u8::from_str_radix(digits.get(i..i + 2)?, 16).ok())
+                .collect::<Option<Vec<u8>>>(),
+            _ => None,
+        }
+        .ok_or_else(invalid)?;
+
+        Ok(Color {
+            r: channels[0] as f32 / 255.0,
+            g: channels[1] as f32 / 255.0,
+            b: channels[2] as f32 / 255.0,
+        })
+    }
+}
+
+fn deserialize_color_stops<'de, D>(deserializer: D) -> Result<Vec<Color>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let colors = Vec::<Color>::deserialize(deserializer)?;
+
+    if colors.is_empty() || colors.len() > MAX_COLOR_STOPS {
+        return Err(serde::de::Error::custom(format!(
+            "A custom color scheme needs between 1 and {} colors",
+            MAX_COLOR_STOPS
+        )));
+    }
+
+    Ok(colors)
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -60,15 +440,170 @@ pub struct Noise {
     pub blend_duration: f32,
     pub blend_threshold: f32,
     pub blend_method: BlendMethod,
+    #[serde(default)]
+    pub kind: NoiseKind,
 }
 
-pub fn color_wheel_from_scheme(color_scheme: &ColorScheme) -> [f32; 24] {
-    match color_scheme {
-        ColorScheme::Plasma => COLOR_SCHEME_PLASMA,
-        ColorScheme::Peacock => COLOR_SCHEME_PEACOCK,
-        ColorScheme::Poolside => COLOR_SCHEME_POOLSIDE,
-        ColorScheme::Pollen => COLOR_SCHEME_POLLEN,
+// The noise generator used to fill a noise channel.
+//
+// All kinds are sampled in 3D, with the channel offsets used as the third
+// coordinate, so that the noise evolves smoothly over time.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub enum NoiseKind {
+    #[default]
+    Simplex,
+    Perlin,
+    // Distance to the nearest feature point. `jitter` controls how far the
+    // points stray from a regular grid, from 0 to 1.
+    #[serde(rename_all = "camelCase")]
+    Worley {
+        jitter: f32,
+    },
+    Value,
+    // Fractal Brownian motion: several octaves of simplex noise, each
+    // `lacunarity` times the frequency and `gain` times the amplitude of the
+    // previous one.
+    #[serde(rename_all = "camelCase")]
+    Fbm {
+        octaves: u32,
+        lacunarity: f32,
+        gain: f32,
+    },
+    // Simplex noise sampled at coordinates displaced by another noise field.
+    #[serde(rename_all = "camelCase")]
+    DomainWarp {
+        warp_frequency: f32,
+        warp_strength: f32,
+    },
+}
+
+// The number of octaves `fbm_noise.frag` can loop over.
+pub const MAX_FBM_OCTAVES: u32 = 8;
+
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct NoiseChannels<T> {
+    noise_channels: Option<T>,
+    #[serde(default, skip_serializing)]
+    noise_channel_1: Option<Noise>,
+    #[serde(default, skip_serializing)]
+    noise_channel_2: Option<Noise>,
+}
+
+fn deserialize_noise_channels<'de, D>(deserializer: D) -> Result<Vec<Noise>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let channels = NoiseChannels::<Vec<Noise>>::deserialize(deserializer)?;
+
+    match channels {
+        NoiseChannels {
+            noise_channels: Some(noise_channels),
+            ..
+        } => Ok(noise_channels),
+        NoiseChannels {
+            noise_channel_1: None,
+            noise_channel_2: None,
+            ..
+        } => Err(serde::de::Error::missing_field("noiseChannels")),
+        NoiseChannels {
+            noise_channel_1,
+            noise_channel_2,
+            ..
+        } => Ok(noise_channel_1.into_iter().chain(noise_channel_2).collect()),
+    }
+}
+
+fn serialize_noise_channels<S>(
+    noise_channels: &Vec<Noise>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    NoiseChannels {
+        noise_channels: Some(noise_channels),
+        noise_channel_1: None,
+        noise_channel_2: None,
     }
+    .serialize(serializer)
+}
+
+// Flatten a color scheme into RGBA values for the `uColorWheel` uniform.
+//
+// The colors are converted to the interpolation space here, so the shader only
+// has to convert the blended color back to sRGB. Deserializing checks the
+// number of custom colors, but settings built in Rust don’t go through that,
+// so an empty custom scheme falls back to `Plasma`, and extra colors past
+// `MAX_COLOR_STOPS` are dropped.
+pub fn color_wheel_from_scheme(
+    color_scheme: &ColorScheme,
+    color_interpolation: &ColorInterpolation,
+) -> Vec<f32> {
+    let colors: Vec<[f32; 3]> = match color_scheme {
+        ColorScheme::Plasma => unpack_color_wheel(&COLOR_SCHEME_PLASMA),
+        ColorScheme::Peacock => unpack_color_wheel(&COLOR_SCHEME_PEACOCK),
+        ColorScheme::Poolside => unpack_color_wheel(&COLOR_SCHEME_POOLSIDE),
+        ColorScheme::Pollen => unpack_color_wheel(&COLOR_SCHEME_POLLEN),
+        ColorScheme::Custom(colors) if colors.is_empty() => {
+            unpack_color_wheel(&COLOR_SCHEME_PLASMA)
+        }
+        ColorScheme::Custom(colors) => colors
+            .iter()
+            .take(MAX_COLOR_STOPS)
+            .map(|color| [color.r, color.g, color.b])
+            .collect(),
+    };
+
+    colors
+        .iter()
+        .flat_map(|&color| {
+            let [x, y, z] = match color_interpolation {
+                ColorInterpolation::Srgb => color,
+                ColorInterpolation::LinearRgb => srgb_to_linear(color),
+                ColorInterpolation::Oklab => linear_to_oklab(srgb_to_linear(color)),
+                ColorInterpolation::Oklch => oklab_to_oklch(linear_to_oklab(srgb_to_linear(color))),
+            };
+            [x, y, z, 1.0]
+        })
+        .collect()
+}
+
+fn unpack_color_wheel(color_wheel: &[f32]) -> Vec<[f32; 3]> {
+    color_wheel
+        .chunks_exact(4)
+        .map(|rgba| [rgba[0], rgba[1], rgba[2]])
+        .collect()
+}
+
+fn srgb_to_linear(color: [f32; 3]) -> [f32; 3] {
+    color.map(|c| {
+        if c <= 0.04045 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    })
+}
+
+// https://bottosson.github.io/posts/oklab/
+fn linear_to_oklab([r, g, b]: [f32; 3]) -> [f32; 3] {
+    let l = 0.41222146 * r + 0.53633255 * g + 0.051445995 * b;
+    let m = 0.2119035 * r + 0.6806995 * g + 0.10739696 * b;
+    let s = 0.08830246 * r + 0.28171885 * g + 0.6299787 * b;
+
+    let (l, m, s) = (l.cbrt(), m.cbrt(), s.cbrt());
+
+    [
+        0.21045426 * l + 0.7936178 * m - 0.004072047 * s,
+        1.9779985 * l - 2.4285922 * m + 0.4505937 * s,
+        0.025904037 * l + 0.78277177 * m - 0.80867577 * s,
+    ]
+}
+
+// Lightness, chroma, and hue in radians
+fn oklab_to_oklch([l, a, b]: [f32; 3]) -> [f32; 3] {
+    [l, a.hypot(b), b.atan2(a)]
 }
 
 #[rustfmt::skip]
@@ -0,0 +1,181 @@
+use crate::{drawer, fluid, flux, noise};
+use drawer::DrawerState;
+use fluid::FluidState;
+use flux::Problem;
+use noise::{NoiseChannelState, NoiseState};
+
+// Marks the start of every snapshot, followed by the format version.
+const MAGIC: &[u8; 4] = b"FLUX";
+const VERSION: u32 = 1;
+
+// Everything needed to resume an animation exactly where it left off: the
+// fluid, the noise channels and the lines. The settings aren’t included, so
+// restore a snapshot with the same settings it was taken with.
+//
+// `to_bytes` packs it into a compact binary format: the header, followed by
+// little-endian 32-bit numbers. Lists are prefixed with their length.
+#[derive(Clone, Debug)]
+pub struct FluxState {
+    pub(crate) elapsed_time: f32,
+    pub(crate) frame_time: f32,
+    pub(crate) fluid: FluidState,
+    pub(crate) noise: NoiseState,
+    pub(crate) drawer: DrawerState,
+}
+
+impl FluxState {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut writer = Writer(Vec::new());
+        writer.0.extend_from_slice(MAGIC);
+        writer.u32(VERSION);
+
+        writer.f32(self.elapsed_time);
+        writer.f32(self.frame_time);
+
+        let fluid = &self.fluid;
+        writer.u32(fluid.width);
+        writer.u32(fluid.height);
+        writer.f32s(&fluid.velocity);
+        writer.f32s(&fluid.pressure);
+        writer.f32s(&fluid.dye);
+        writer.f32s(&fluid.temperature);
+
+        let noise = &self.noise;
+        writer.u32(noise.width);
+        writer.u32(noise.height);
+        writer.u32(noise.channels.len() as u32);
+        for channel in noise.channels.iter() {
+            writer.f32(channel.blend_begin_time);
+            writer.f32(channel.last_blend_progress);
+            writer.f32(channel.offset1);
+            writer.f32(channel.offset2);
+            writer.f32s(&channel.texture);
+        }
+
+        writer.u32(self.drawer.line_count);
+        writer.f32s(&self.drawer.line_state);
+
+        writer.0
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Problem> {
+        let mut reader = Reader(bytes);
+
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(cannot_read("this isn’t a Flux snapshot"));
+        }
+        let version = reader.u32()?;
+        if version != VERSION {
+            return Err(cannot_read(&format!(
+                "unsupported snapshot version {}",
+                version
+            )));
+        }
+
+        let elapsed_time = reader.f32()?;
+        let frame_time = reader.f32()?;
+
+        let fluid = FluidState {
+            width: reader.u32()?,
+            height: reader.u32()?,
+            velocity: reader.f32s()?,
+            pressure: reader.f32s()?,
+            dye: reader.f32s()?,
+            temperature: reader.f32s()?,
+        };
+
+        let noise_width = reader.u32()?;
+        let noise_height = reader.u32()?;
+        let channel_count = reader.u32()?;
+        let mut channels = Vec::new();
+        for _ in 0..channel_count {
+            channels.push(NoiseChannelState {
+                blend_begin_time: reader.f32()?,
+                last_blend_progress: reader.f32()?,
+                offset1: reader.f32()?,
+                offset2: reader.f32()?,
+                texture: reader.f32s()?,
+            });
+        }
+        let noise = NoiseState {
+            width: noise_width,
+            height: noise_height,
+            channels,
+        };
+
+        let drawer = DrawerState {
+            line_count: reader.u32()?,
+            line_state: reader.f32s()?,
+        };
+
+        if !reader.0.is_empty() {
+            return Err(cannot_read("unexpected data at the end"));
+        }
+
+        Ok(Self {
+            elapsed_time,
+            frame_time,
+            fluid,
+            noise,
+            drawer,
+        })
+    }
+}
+
+fn cannot_read(reason: &str) -> Problem {
+    Problem::CannotReadState(format!("Cannot read the snapshot: {}", reason))
+}
+
+struct Writer(Vec<u8>);
+
+impl Writer {
+    fn u32(&mut self, value: u32) -> () {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn f32(&mut self, value: f32) -> () {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn f32s(&mut self, values: &[f32]) -> () {
+        self.u32(values.len() as u32);
+        for value in values {
+            self.f32(*value);
+        }
+    }
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, count: usize) -> Result<&'a [u8], Problem> {
+        if self.0.len() < count {
+            return Err(cannot_read("it ends too early"));
+        }
+
+        let (bytes, rest) = self.0.split_at(count);
+        self.0 = rest;
+        Ok(bytes)
+    }
+
+    fn u32(&mut self) -> Result<u32, Problem> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn f32(&mut self) -> Result<f32, Problem> {
+        Ok(f32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn f32s(&mut self) -> Result<Vec<f32>, Problem> {
+        let count = self.u32()? as usize;
+        let size = count
+            .checked_mul(4)
+            .ok_or_else(|| cannot_read("it has too many values"))?;
+        let bytes = self.take(size)?;
+
+        Ok(bytes
+            .chunks_exact(4)
+            .map(|chunk| f32::from_le_bytes(chunk.try_into().unwrap()))
+            .collect())
+    }
+}
@@ -0,0 +1,888 @@
+// Golden-image tests for the renderer.
+//
+// Each case renders a fixed number of frames through a software GL context
+// and compares the final frame to a reference image in `tests/golden`. The
+// comparison is done in CIELAB, so small rounding differences between drivers
+// pass, but visible changes to the output don’t.
+//
+// To update the reference images after an intentional change, run:
+//
+//     FLUX_BLESS=1 cargo test -p flux --features headless --release --test golden
+//
+// Failed comparisons write the rendered frame and a difference mask to
+// `target/tmp/golden`.
+
+use flux::headless::HeadlessFlux;
+use flux::settings::{
+    AdvectionScheme, BlendMethod, Boundary, Color, ColorInterpolation, ColorScheme, Dye,
+    DyeBlendMode, Noise, NoiseKind, Obstacle, PressureSolver, Settings, Temperature,
+};
+use flux::{CpuFluid, Field, FieldData, FieldFormat, FluxState};
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+const WIDTH: u32 = 320;
+const HEIGHT: u32 = 200;
+const FRAME_COUNT: u32 = 240;
+
+// A pixel is considered different if the color difference is above this
+// threshold. A ΔE of around 2.3 is the “just noticeable difference”.
+const MAX_DELTA_E: f32 = 6.0;
+// The share of pixels allowed to differ before the comparison fails.
+const MAX_DIFFERENT_PIXELS: f32 = 0.005;
+
+struct Case {
+    name: &'static str,
+    settings: Settings,
+}
+
+fn base_settings() -> Settings {
+    Settings {
+        viscosity: 1.0,
+        velocity_dissipation: 0.0,
+        starting_pressure: 0.8,
+        fluid_size: 128,
+        fluid_simulation_frame_rate: 30.0,
+        diffusion_iterations: 20,
+        pressure_iterations: 60,
+        advection_scheme: AdvectionScheme::SemiLagrangian,
+        pressure_solver: PressureSolver::Jacobi,
+        warm_start_pressure: false,
+        pressure_tolerance: 0.0,
+        boundary: Boundary::FreeSlip,
+        vorticity_strength: 0.0,
+        color_scheme: ColorScheme::Plasma,
+        color_interpolation: ColorInterpolation::Srgb,
+        line_length: 180.0,
+        line_width: 6.0,
+        line_begin_offset: 0.5,
+        line_fade_out_length: 0.005,
+        spring_stiffness: 0.2,
+        spring_variance: 0.25,
+        spring_mass: 2.0,
+        spring_damping: 2.0,
+        spring_rest_length: 0.0,
+        advection_direction: 1.0,
+        adjust_advection: 22.0,
+        max_line_velocity: 0.02,
+        grid_spacing: 20,
+        view_scale: 1.2,
+        pointer_radius: 80.0,
+        pointer_strength: 0.4,
+        obstacles: Vec::new(),
+        dye: None,
+        temperature: None,
+        noise_channels: vec![
+            Noise {
+                scale: 0.9,
+                multiplier: 0.20,
+                offset_1: 2.0,
+                offset_2: 8.0,
+                offset_increment: 0.01,
+                delay: 0.5,
+                blend_duration: 3.5,
+                blend_threshold: 0.4,
+                blend_method: BlendMethod::Curl,
+                kind: NoiseKind::Simplex,
+            },
+            Noise {
+                scale: 25.0,
+                multiplier: 0.08,
+                offset_1: 3.0,
+                offset_2: 2.0,
+                offset_increment: 0.02,
+                delay: 0.15,
+                blend_duration: 1.0,
+                blend_threshold: 0.0,
+                blend_method: BlendMethod::Curl,
+                kind: NoiseKind::Simplex,
+            },
+        ],
+    }
+}
+
+fn with_blend_methods(blend_methods: &[BlendMethod]) -> Settings {
+    let settings = base_settings();
+    Settings {
+        noise_channels: settings
+            .noise_channels
+            .iter()
+            .zip(blend_methods)
+            .map(|(noise, blend_method)| Noise {
+                blend_method: blend_method.clone(),
+                ..noise.clone()
+            })
+            .collect(),
+        ..settings
+    }
+}
+
+// Swap the generator of the first, large-scale noise channel, which shapes
+// most of the flow.
+fn with_noise_kind(kind: NoiseKind) -> Settings {
+    let mut settings = base_settings();
+    settings.noise_channels[0].kind = kind;
+    settings
+}
+
+fn cases() -> Vec<Case> {
+    vec![
+        Case {
+            name: "plasma",
+            settings: Settings {
+                color_scheme: ColorScheme::Plasma,
+                ..base_settings()
+            },
+        },
+        Case {
+            name: "peacock",
+            settings: Settings {
+                color_scheme: ColorScheme::Peacock,
+                ..base_settings()
+            },
+        },
+        Case {
+            name: "poolside",
+            settings: Settings {
+                color_scheme: ColorScheme::Poolside,
+                ..base_settings()
+            },
+        },
+        Case {
+            name: "pollen",
+            settings: Settings {
+                color_scheme: ColorScheme::Pollen,
+                ..base_settings()
+            },
+        },
+        Case {
+            name: "custom_color_scheme",
+            settings: Settings {
+                color_scheme: ColorScheme::Custom(
+                    [
+                        "#ff5f6d", "#ffc371", "#47cacc", "#63bcc9", "#cdb3d4", "#e7b7c8", "#ffbe88",
+                    ]
+                    .iter()
+                    .map(|hex| Color::from_hex(hex).unwrap())
+                    .collect(),
+                ),
+                ..base_settings()
+            },
+        },
+        Case {
+            name: "peacock_linear_rgb",
+            settings: Settings {
+                color_scheme: ColorScheme::Peacock,
+                color_interpolation: ColorInterpolation::LinearRgb,
+                ..base_settings()
+            },
+        },
+        Case {
+            name: "peacock_oklab",
+            settings: Settings {
+                color_scheme: ColorScheme::Peacock,
+                color_interpolation: ColorInterpolation::Oklab,
+                ..base_settings()
+            },
+        },
+        Case {
+            name: "peacock_oklch",
+            settings: Settings {
+                color_scheme: ColorScheme::Peacock,
+                color_interpolation: ColorInterpolation::Oklch,
+                ..base_settings()
+            },
+        },
+        // Wiggle only bends the existing flow, so pair it with curl noise.
+        Case {
+            name: "curl_then_wiggle",
+            settings: with_blend_methods(&[BlendMethod::Curl, BlendMethod::Wiggle]),
+        },
+        Case {
+            name: "wiggle_then_curl",
+            settings: with_blend_methods(&[BlendMethod::Wiggle, BlendMethod::Curl]),
+        },
+        Case {
+            name: "perlin_noise",
+            settings: with_noise_kind(NoiseKind::Perlin),
+        },
+        Case {
+            name: "worley_noise",
+            settings: with_noise_kind(NoiseKind::Worley { jitter: 0.8 }),
+        },
+        Case {
+            name: "value_noise",
+            settings: with_noise_kind(NoiseKind::Value),
+        },
+        Case {
+            name: "fbm_noise",
+            settings: with_noise_kind(NoiseKind::Fbm {
+                octaves: 4,
+                lacunarity: 2.0,
+                gain: 0.5,
+            }),
+        },
+        Case {
+            name: "domain_warp_noise",
+            settings: with_noise_kind(NoiseKind::DomainWarp {
+                warp_frequency: 0.6,
+                warp_strength: 0.8,
+            }),
+        },
+        Case {
+            name: "vorticity_confinement",
+            settings: Settings {
+                vorticity_strength: 10.0,
+                ..base_settings()
+            },
+        },
+        Case {
+            name: "maccormack_advection",
+            settings: Settings {
+                advection_scheme: AdvectionScheme::MacCormack,
+                ..base_settings()
+            },
+        },
+        Case {
+            name: "bfecc_advection",
+            settings: Settings {
+                advection_scheme: AdvectionScheme::Bfecc,
+                ..base_settings()
+            },
+        },
+        Case {
+            name: "multigrid_pressure",
+            settings: Settings {
+                pressure_solver: PressureSolver::Multigrid {
+                    cycles: 2,
+                    smoothing_iterations: 3,
+                },
+                ..base_settings()
+            },
+        },
+        Case {
+            name: "warm_started_pressure",
+            settings: Settings {
+                warm_start_pressure: true,
+                pressure_tolerance: 0.001,
+                ..base_settings()
+            },
+        },
+        Case {
+            name: "obstacles",
+            settings: Settings {
+                obstacles: vec![
+                    Obstacle::Circle {
+                        center: [0.25, 0.6],
+                        radius: 0.15,
+                    },
+                    Obstacle::Rectangle {
+                        center: [0.7, 0.3],
+                        size: [0.4, 0.15],
+                        corner_radius: 0.05,
+                    },
+                    // A triangle with a triangular hole
+                    Obstacle::Outline {
+                        position: [0.75, 0.7],
+                        contours: vec![
+                            vec![[-0.15, -0.1], [0.15, -0.1], [0.0, 0.15]],
+                            vec![[-0.06, -0.05], [0.06, -0.05], [0.0, 0.05]],
+                        ],
+                    },
+                ],
+                // Shorter lines leave the outlines of the obstacles visible
+                line_length: 60.0,
+                ..base_settings()
+            },
+        },
+        Case {
+            name: "no_slip_boundary",
+            settings: Settings {
+                boundary: Boundary::NoSlip,
+                ..base_settings()
+            },
+        },
+        Case {
+            name: "periodic_boundary",
+            settings: Settings {
+                boundary: Boundary::Periodic,
+                ..base_settings()
+            },
+        },
+        Case {
+            name: "open_boundary",
+            settings: Settings {
+                boundary: Boundary::Open,
+                ..base_settings()
+            },
+        },
+        Case {
+            name: "dye",
+            settings: Settings {
+                dye: Some(Dye {
+                    color: Color::from_hex("#4a6cd4").unwrap(),
+                    dissipation: 0.2,
+                    noise_amount: 2.0,
+                    splat_amount: 0.0,
+                    opacity: 0.8,
+                    blend_mode: DyeBlendMode::Screen,
+                }),
+                ..base_settings()
+            },
+        },
+        Case {
+            name: "rising_heat",
+            settings: Settings {
+                temperature: Some(Temperature {
+                    dissipation: 0.5,
+                    buoyancy: 0.4,
+                    noise_amount: 4.0,
+                    splat_amount: 0.0,
+                }),
+                ..base_settings()
+            },
+        },
+        Case {
+            name: "zoomed_in_dense_grid",
+            settings: Settings {
+                view_scale: 2.0,
+                grid_spacing: 12,
+                ..base_settings()
+            },
+        },
+        Case {
+            name: "zoomed_out_sparse_grid",
+            settings: Settings {
+                view_scale: 0.8,
+                grid_spacing: 30,
+                ..base_settings()
+            },
+        },
+        Case {
+            name: "unscaled_default_grid",
+            settings: Settings {
+                view_scale: 1.0,
+                grid_spacing: 20,
+                ..base_settings()
+            },
+        },
+    ]
+}
+
+// The fallbacks for GPUs that can’t render to 32-bit float textures. These use
+// dye and heat to cover every kind of texture the simulation uses.
+fn field_format_cases() -> Vec<(Case, Option<FieldFormat>)> {
+    let settings = || Settings {
+        dye: Some(Dye {
+            color: Color::from_hex("#4a6cd4").unwrap(),
+            dissipation: 0.2,
+            noise_amount: 2.0,
+            splat_amount: 0.0,
+            opacity: 0.8,
+            blend_mode: DyeBlendMode::Screen,
+        }),
+        temperature: Some(Temperature {
+            dissipation: 0.5,
+            buoyancy: 0.4,
+            noise_amount: 1.0,
+            splat_amount: 0.0,
+        }),
+        ..base_settings()
+    };
+
+    vec![
+        (
+            Case {
+                name: "half_float_fields",
+                settings: settings(),
+            },
+            Some(FieldFormat::Float16),
+        ),
+        (
+            Case {
+                name: "packed_fields",
+                settings: settings(),
+            },
+            Some(FieldFormat::Packed8),
+        ),
+        // Multigrid resamples the residual between levels, which has to
+        // unpack it first.
+        (
+            Case {
+                name: "packed_multigrid",
+                settings: Settings {
+                    pressure_solver: PressureSolver::Multigrid {
+                        cycles: 2,
+                        smoothing_iterations: 3,
+                    },
+                    ..settings()
+                },
+            },
+            Some(FieldFormat::Packed8),
+        ),
+    ]
+}
+
+#[test]
+fn renders_match_reference_images() {
+    let bless = std::env::var_os("FLUX_BLESS").is_some();
+    let mut failures = Vec::new();
+
+    let all_cases = cases()
+        .into_iter()
+        .map(|case| (case, None))
+        .chain(field_format_cases());
+
+    for (case, field_format) in all_cases {
+        let mut flux =
+            HeadlessFlux::with_field_format(WIDTH, HEIGHT, &Rc::new(case.settings), field_format)
+                .unwrap_or_else(|err| panic!("Cannot start Flux for `{}`: {}", case.name, err));
+        let frame = flux.render_frames(FRAME_COUNT).pop().unwrap();
+        drop(flux);
+
+        let reference_path = golden_dir().join(format!("{}.png", case.name));
+
+        if bless {
+            write_png(&reference_path, &frame);
+            continue;
+        }
+
+        let reference = match read_png(&reference_path) {
+            Some(reference) => reference,
+            None => {
+                failures.push(format!(
+                    "{}: missing reference image {}",
+                    case.name,
+                    reference_path.display()
+                ));
+                continue;
+            }
+        };
+
+        let comparison = compare(&reference, &frame);
+        if comparison.different_pixels > MAX_DIFFERENT_PIXELS {
+            let output_dir = output_dir();
+            fs::create_dir_all(&output_dir).unwrap();
+            write_png(&output_dir.join(format!("{}.png", case.name)), &frame);
+            write_png(
+                &output_dir.join(format!("{}.diff.png", case.name)),
+                &comparison.mask,
+            );
+
+            failures.push(format!(
+                "{}: {:.2}% of pixels differ (max ΔE {:.1}). See {}",
+                case.name,
+                100.0 * comparison.different_pixels,
+                comparison.max_delta_e,
+                output_dir.display(),
+            ));
+        }
+    }
+
+    assert!(
+        failures.is_empty(),
+        "Rendered frames don’t match the reference images:\n{}",
+        failures.join("\n")
+    );
+}
+
+struct Comparison {
+    different_pixels: f32,
+    max_delta_e: f32,
+    mask: Vec<u8>,
+}
+
+fn compare(reference: &[u8], frame: &[u8]) -> Comparison {
+    let mut different = 0;
+    let mut max_delta_e: f32 = 0.0;
+    let mut mask = Vec::with_capacity(frame.len());
+
+    for (expected, actual) in reference.chunks_exact(4).zip(frame.chunks_exact(4)) {
+        let delta_e = delta_e(to_lab(expected), to_lab(actual));
+        max_delta_e = max_delta_e.max(delta_e);
+
+        if delta_e > MAX_DELTA_E {
+            different += 1;
+            mask.extend_from_slice(&[255, 0, 0, 255]);
+        } else {
+            // Dim the original so the differences stand out.
+            mask.extend_from_slice(&[actual[0] / 4, actual[1] / 4, actual[2] / 4, 255]);
+        }
+    }
+
+    Comparison {
+        different_pixels: different as f32 / (WIDTH * HEIGHT) as f32,
+        max_delta_e,
+        mask,
+    }
+}
+
+// CIE76 color difference
+fn delta_e(a: [f32; 3], b: [f32; 3]) -> f32 {
+    ((a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2) + (a[2] - b[2]).powi(2)).sqrt()
+}
+
+// sRGB to CIELAB with a D65 white point
+fn to_lab(pixel: &[u8]) -> [f32; 3] {
+    let linear = |c: u8| {
+        let c = c as f32 / 255.0;
+        if c <= 0.04045 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    let (r, g, b) = (linear(pixel[0]), linear(pixel[1]), linear(pixel[2]));
+
+    let x = (0.4124 * r + 0.3576 * g + 0.1805 * b) / 0.95047;
+    let y = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+    let z = (0.0193 * r + 0.1192 * g + 0.9505 * b) / 1.08883;
+
+    let f = |t: f32| {
+        if t > 0.008856 {
+            t.cbrt()
+        } else {
+            7.787 * t + 16.0 / 116.0
+        }
+    };
+    let (fx, fy, fz) = (f(x), f(y), f(z));
+
+    [116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz)]
+}
+
+fn golden_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden")
+}
+
+fn output_dir() -> PathBuf {
+    Path::new(env!("CARGO_TARGET_TMPDIR")).join("golden")
+}
+
+fn read_png(path: &Path) -> Option<Vec<u8>> {
+    let decoder = png::Decoder::new(File::open(path).ok()?);
+    let mut reader = decoder.read_info().ok()?;
+    let mut pixels = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut pixels).ok()?;
+
+    if info.width != WIDTH || info.height != HEIGHT || info.color_type != png::ColorType::Rgba {
+        return None;
+    }
+
+    pixels.truncate(info.buffer_size());
+    Some(pixels)
+}
+
+fn write_png(path: &Path, pixels: &[u8]) {
+    let file = File::create(path).unwrap();
+    let mut encoder = png::Encoder::new(BufWriter::new(file), WIDTH, HEIGHT);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder
+        .write_header()
+        .unwrap()
+        .write_image_data(pixels)
+        .unwrap();
+}
+
+// A snapshot, saved as bytes and restored into a fresh instance, should pick up
+// exactly where the original left off.
+#[test]
+fn restored_snapshot_resumes_the_animation() {
+    let settings = Rc::new(base_settings());
+    let mut original = HeadlessFlux::new(WIDTH, HEIGHT, &settings).unwrap();
+    original.render_frames(FRAME_COUNT / 2);
+    let bytes = original.snapshot().unwrap().to_bytes();
+    let expected = original.render_frames(FRAME_COUNT / 4).pop().unwrap();
+    drop(original);
+
+    let mut restored = HeadlessFlux::new(WIDTH, HEIGHT, &settings).unwrap();
+    restored
+        .restore(&FluxState::from_bytes(&bytes).unwrap())
+        .unwrap();
+    let actual = restored.render_frames(FRAME_COUNT / 4).pop().unwrap();
+    drop(restored);
+
+    let comparison = compare(&expected, &actual);
+    assert!(
+        comparison.different_pixels <= MAX_DIFFERENT_PIXELS,
+        "{:.2}% of pixels differ after restoring (max ΔE {:.1})",
+        100.0 * comparison.different_pixels,
+        comparison.max_delta_e,
+    );
+}
+
+// A snapshot can’t be restored with settings that have a different number of
+// noise channels.
+#[test]
+fn rejects_snapshot_with_other_noise_channels() {
+    let settings = base_settings();
+    let one_channel = Settings {
+        noise_channels: settings.noise_channels[..1].to_vec(),
+        ..base_settings()
+    };
+
+    let original = HeadlessFlux::new(WIDTH, HEIGHT, &Rc::new(settings)).unwrap();
+    let bytes = original.snapshot().unwrap().to_bytes();
+    drop(original);
+
+    let mut restored = HeadlessFlux::new(WIDTH, HEIGHT, &Rc::new(one_channel)).unwrap();
+    assert!(restored
+        .restore(&FluxState::from_bytes(&bytes).unwrap())
+        .is_err());
+}
+
+// A snapshot whose fields don’t match their size is rejected before any of it
+// is restored.
+#[test]
+fn rejects_snapshot_with_wrong_field_sizes() {
+    let settings = Rc::new(base_settings());
+    let mut original = HeadlessFlux::new(WIDTH, HEIGHT, &settings).unwrap();
+    original.render_frames(FRAME_COUNT / 4);
+    let mut bytes = original.snapshot().unwrap().to_bytes();
+    drop(original);
+
+    // Widen the fluid grid, which comes after the header and the timings
+    let width = u32::from_le_bytes(bytes[16..20].try_into().unwrap());
+    bytes[16..20].copy_from_slice(&(width + 1).to_le_bytes());
+
+    let mut restored = HeadlessFlux::new(WIDTH, HEIGHT, &settings).unwrap();
+    let noise = restored.read_field(Field::Noise(0)).unwrap();
+    assert!(restored
+        .restore(&FluxState::from_bytes(&bytes).unwrap())
+        .is_err());
+    assert_eq!(
+        restored.read_field(Field::Noise(0)).unwrap().data,
+        noise.data
+    );
+}
+
+// Fields read back from the GPU should have the size of the simulation and
+// hold the flow the noise has set up.
+#[test]
+fn reads_back_simulation_fields() {
+    let mut flux = HeadlessFlux::new(WIDTH, HEIGHT, &Rc::new(base_settings())).unwrap();
+    flux.render_frames(FRAME_COUNT / 4);
+
+    let velocity = flux.read_field(Field::Velocity).unwrap();
+    assert_eq!(velocity.channels, 2);
+    assert_eq!(
+        velocity.data.len(),
+        (velocity.width * velocity.height * velocity.channels) as usize
+    );
+    assert!(velocity.data.iter().all(|value| value.is_finite()));
+    assert!(velocity.data.iter().any(|value| *value != 0.0));
+
+    let noise = flux.read_field(Field::Noise(0)).unwrap();
+    assert!(noise.width < velocity.width && noise.height < velocity.height);
+    assert!(flux.read_field(Field::Noise(2)).is_err());
+}
+
+// Resizing stretches the fields to the new size. Packed fields have to be
+// unpacked to be filtered, or their high and low bytes get mixed up.
+#[test]
+fn resizing_stretches_packed_fields() {
+    let settings = Rc::new(base_settings());
+    let mut flux =
+        HeadlessFlux::with_field_format(WIDTH, HEIGHT, &settings, Some(FieldFormat::Packed8))
+            .unwrap();
+    flux.render_frames(FRAME_COUNT / 4);
+    let before = flux.read_field(Field::Velocity).unwrap();
+
+    flux.resize(HEIGHT, HEIGHT).unwrap();
+    let after = flux.read_field(Field::Velocity).unwrap();
+    assert_ne!(after.width, before.width);
+
+    let mut max_difference: f32 = 0.0;
+    for y in 0..after.height {
+        for x in 0..after.width {
+            let expected = sample_bilinear(
+                &before,
+                (x as f32 + 0.5) / after.width as f32,
+                (y as f32 + 0.5) / after.height as f32,
+            );
+            for (a, b) in after.get(x, y).iter().zip(expected) {
+                max_difference = max_difference.max((a - b).abs());
+            }
+        }
+    }
+    assert!(
+        max_difference < PACKED_FIELD_TOLERANCE,
+        "The resized velocity is off by up to {}",
+        max_difference
+    );
+}
+
+// How far a resized packed field may be from the filtered original. This is an
+// absolute bound of a few steps of the 16-bit fixed point the values are
+// packed into, which are 4 / 32768 apart.
+const PACKED_FIELD_TOLERANCE: f32 = 1e-3;
+
+// Like a linear texture lookup with clamped edges.
+fn sample_bilinear(field: &FieldData, s: f32, t: f32) -> [f32; 2] {
+    let x = (s * field.width as f32 - 0.5).max(0.0);
+    let y = (t * field.height as f32 - 0.5).max(0.0);
+    let (x0, y0) = (x.floor() as u32, y.floor() as u32);
+    let (x1, y1) = (
+        (x0 + 1).min(field.width - 1),
+        (y0 + 1).min(field.height - 1),
+    );
+    let (fx, fy) = (x.fract(), y.fract());
+
+    [0, 1].map(|i| {
+        let bottom = field.get(x0, y0)[i] * (1.0 - fx) + field.get(x1, y0)[i] * fx;
+        let top = field.get(x0, y1)[i] * (1.0 - fx) + field.get(x1, y1)[i] * fx;
+        bottom * (1.0 - fy) + top * fy
+    })
+}
+
+// Multigrid should leave less divergence behind than Jacobi iterations on the
+// full grid, or it isn’t worth the extra passes.
+#[test]
+fn multigrid_solves_pressure_more_accurately_than_jacobi() {
+    let pressure_residual = |pressure_solver| {
+        let settings = Settings {
+            pressure_solver,
+            ..base_settings()
+        };
+        let mut flux = HeadlessFlux::new(WIDTH, HEIGHT, &Rc::new(settings)).unwrap();
+        flux.render_frames(FRAME_COUNT / 4);
+        flux.pressure_residual()
+    };
+
+    let jacobi = pressure_residual(PressureSolver::Jacobi);
+    let multigrid = pressure_residual(PressureSolver::Multigrid {
+        cycles: 4,
+        smoothing_iterations: 3,
+    });
+    assert!(
+        multigrid < jacobi,
+        "Multigrid residual {} isn’t below the Jacobi residual {}",
+        multigrid,
+        jacobi
+    );
+}
+
+// Resizing and updating the settings shouldn’t leak GL objects, and dropping
+// Flux should delete all of them.
+#[cfg(debug_assertions)]
+#[test]
+fn frees_gl_objects() {
+    let before = flux::live_gl_objects();
+    let settings = Rc::new(base_settings());
+    let mut flux = HeadlessFlux::new(WIDTH, HEIGHT, &settings).unwrap();
+    flux.render_frames(10);
+    let live = flux::live_gl_objects();
+    assert!(live > before);
+
+    for (width, height) in [(2 * WIDTH, 3 * HEIGHT), (WIDTH, HEIGHT)] {
+        flux.resize(width, height).unwrap();
+        flux.update(&settings).unwrap();
+        flux.read_field(Field::Velocity).unwrap();
+        flux.render_frames(10);
+    }
+    assert_eq!(flux::live_gl_objects(), live);
+
+    drop(flux);
+    assert_eq!(flux::live_gl_objects(), before);
+}
+
+#[test]
+fn probes_device_capabilities() {
+    let flux = HeadlessFlux::new(WIDTH, HEIGHT, &Rc::new(base_settings())).unwrap();
+    let capabilities = flux.capabilities();
+
+    assert!(!capabilities.gl_version.is_empty());
+    assert!(!capabilities.renderer.is_empty());
+    assert!(capabilities.max_texture_size >= 2048);
+    assert!(capabilities.supports_transform_feedback);
+    assert_eq!(flux.field_format(), FieldFormat::detect(capabilities));
+    assert!(capabilities.to_string().contains(&capabilities.renderer));
+}
+
+// The CPU solver is a reference for the shaders, so a fluid step on each,
+// starting from the same velocity, should give the same fields.
+#[test]
+fn cpu_solver_matches_gpu_solver() {
+    let variants: [fn() -> Settings; 3] = [
+        base_settings,
+        || Settings {
+            advection_scheme: AdvectionScheme::MacCormack,
+            vorticity_strength: 0.3,
+            ..base_settings()
+        },
+        || Settings {
+            pressure_solver: PressureSolver::Multigrid {
+                cycles: 4,
+                smoothing_iterations: 3,
+            },
+            boundary: Boundary::NoSlip,
+            ..base_settings()
+        },
+    ];
+
+    for settings in variants {
+        let timestep = 1.0 / settings().fluid_simulation_frame_rate;
+        let mut flux = HeadlessFlux::new(WIDTH, HEIGHT, &Rc::new(settings())).unwrap();
+        // One fluid step per frame
+        flux.frame_time = timestep;
+        flux.render_frames(FRAME_COUNT / 4);
+
+        // Stop adding noise, so that only the solver moves the fluid
+        let settings = Rc::new(Settings {
+            noise_channels: Vec::new(),
+            ..settings()
+        });
+        flux.update(&settings).unwrap();
+
+        let mut cpu_fluid = CpuFluid::new(WIDTH as f32 / HEIGHT as f32, &settings);
+        let velocity = flux.read_field(Field::Velocity).unwrap();
+        assert_eq!(
+            (velocity.width, velocity.height),
+            (cpu_fluid.width, cpu_fluid.height)
+        );
+        for (cell, texel) in cpu_fluid
+            .get_velocity_mut()
+            .iter_mut()
+            .zip(velocity.data.chunks_exact(2))
+        {
+            *cell = [texel[0], texel[1]];
+        }
+
+        flux.render_frame();
+        cpu_fluid.step(timestep);
+
+        let previous_velocity = velocity;
+        let velocity = flux.read_field(Field::Velocity).unwrap();
+        let pressure = flux.read_field(Field::Pressure).unwrap();
+        // Make sure the step moved the fluid by well over the tolerance
+        assert!(max_difference(&velocity.data, &previous_velocity.data) > 1e-3);
+        assert_fields_match("velocity", &velocity.data, cpu_fluid.get_velocity());
+        assert_fields_match("pressure", &pressure.data, cpu_fluid.get_pressure());
+    }
+}
+
+// How far the CPU solver may drift from the GPU one, as a share of the largest
+// value in the field. They agree to around 1e-5 on llvmpipe.
+const FIELD_TOLERANCE: f32 = 1e-3;
+
+fn assert_fields_match(name: &str, gpu: &[f32], cpu: &[[f32; 2]]) {
+    let scale = gpu.iter().fold(0.0_f32, |max, value| max.max(value.abs()));
+    let error = max_difference(gpu, cpu.as_flattened());
+
+    assert!(scale > 0.0, "The {} field is empty", name);
+    assert!(
+        error <= FIELD_TOLERANCE * scale,
+        "The {} fields differ by up to {}, out of {}",
+        name,
+        error,
+        scale
+    );
+}
+
+fn max_difference(a: &[f32], b: &[f32]) -> f32 {
+    a.iter()
+        .zip(b)
+        .fold(0.0_f32, |max, (a, b)| max.max((a - b).abs()))
+}
@@ -0,0 +1,102 @@
+// Tests for reading and writing settings in the format the web app sends.
+
+use flux::settings::Settings;
+use serde_json::{json, Value};
+
+// Settings as saved before noise channels became a list.
+fn legacy_settings() -> Value {
+    json!({
+        "viscosity": 5.0,
+        "velocityDissipation": 0.0,
+        "startingPressure": 0.0,
+        "fluidSize": 128,
+        "fluidSimulationFrameRate": 60.0,
+        "diffusionIterations": 4,
+        "pressureIterations": 20,
+        "colorScheme": "Peacock",
+        "lineLength": 550.0,
+        "lineWidth": 10.0,
+        "lineBeginOffset": 0.4,
+        "lineFadeOutLength": 0.001,
+        "springStiffness": 0.2,
+        "springVariance": 0.25,
+        "springMass": 2.0,
+        "springDamping": 2.0,
+        "springRestLength": 0.0,
+        "advectionDirection": 1.0,
+        "adjustAdvection": 22.0,
+        "maxLineVelocity": 0.02,
+        "gridSpacing": 15,
+        "viewScale": 1.6,
+        "noiseChannel1": {
+            "scale": 2.5,
+            "multiplier": 1.0,
+            "offset1": 2.0,
+            "offset2": 10.0,
+            "offsetIncrement": 0.01,
+            "delay": 0.5,
+            "blendDuration": 3.5,
+            "blendThreshold": 0.4,
+            "blendMethod": "Curl",
+        },
+        "noiseChannel2": {
+            "scale": 15.0,
+            "multiplier": 0.7,
+            "offset1": 3.0,
+            "offset2": 6.0,
+            "offsetIncrement": 0.1,
+            "delay": 0.15,
+            "blendDuration": 1.0,
+            "blendThreshold": 0.0,
+            "blendMethod": "Wiggle",
+        },
+    })
+}
+
+#[test]
+fn reads_legacy_noise_channels_in_order() {
+    let settings: Settings = serde_json::from_value(legacy_settings()).unwrap();
+
+    let scales: Vec<f32> = settings
+        .noise_channels
+        .iter()
+        .map(|noise| noise.scale)
+        .collect();
+    assert_eq!(scales, [2.5, 15.0]);
+}
+
+#[test]
+fn writes_legacy_noise_channels_as_a_list() {
+    let settings: Settings = serde_json::from_value(legacy_settings()).unwrap();
+    let saved = serde_json::to_value(&settings).unwrap();
+
+    assert!(saved.get("noiseChannel1").is_none());
+    assert!(saved.get("noiseChannel2").is_none());
+    assert_eq!(saved["noiseChannels"].as_array().map(Vec::len), Some(2));
+
+    let reloaded: Settings = serde_json::from_value(saved.clone()).unwrap();
+    assert_eq!(serde_json::to_value(&reloaded).unwrap(), saved);
+}
+
+#[test]
+fn rejects_settings_without_noise_channels() {
+    let mut settings = legacy_settings();
+    let fields = settings.as_object_mut().unwrap();
+    fields.remove("noiseChannel1");
+    fields.remove("noiseChannel2");
+
+    let error = serde_json::from_value::<Settings>(settings).unwrap_err();
+    assert!(error.to_string().contains("noiseChannels"), "{}", error);
+}
+
+#[test]
+fn accepts_an_empty_list_of_noise_channels() {
+    let mut settings = legacy_settings();
+    let fields = settings.as_object_mut().unwrap();
+    fields.remove("noiseChannel1");
+    fields.remove("noiseChannel2");
+    fields.insert("noiseChannels".to_string(), json!([]));
+
+    let settings: Settings = serde_json::from_value(settings).unwrap();
+    assert!(settings.noise_channels.is_empty());
+}
@@ -1060,8 +1060,7 @@ encodeSettings settings =
         , ( "adjustAdvection", Encode.float settings.adjustAdvection )
         , ( "gridSpacing", Encode.int settings.gridSpacing )
         , ( "viewScale", Encode.float settings.viewScale )
-        , ( "noiseChannel1", encodeNoise settings.noiseChannel1 )
-        , ( "noiseChannel2", encodeNoise settings.noiseChannel2 )
+        , ( "noiseChannels", Encode.list encodeNoise [ settings.noiseChannel1, settings.noiseChannel2 ] )
         ]
 
 
@@ -24,6 +24,28 @@ function setupFlux() {
     });
     resizeObserver.observe(document.getElementById("canvas"));
 
+    // Pointer events cover the mouse, pens, and each finger on a touchscreen.
+    const canvas = document.getElementById("canvas");
+    // Only push the fluid while a button is held, not on every hover.
+    canvas.addEventListener("pointermove", (event) => {
+      if (event.buttons & 1) {
+        flux.move_pointer(event.pointerId, event.offsetX, event.offsetY);
+      }
+    });
+    canvas.addEventListener("pointerdown", (event) => {
+      if (event.button === 2) {
+        flux.twirl_at(event.offsetX, event.offsetY);
+      } else {
+        flux.move_pointer(event.pointerId, event.offsetX, event.offsetY);
+      }
+    });
+    for (const type of ["pointerup", "pointercancel", "pointerleave"]) {
+      canvas.addEventListener(type, (event) => {
+        flux.release_pointer(event.pointerId);
+      });
+    }
+    canvas.addEventListener("contextmenu", (event) => event.preventDefault());
+
     window.requestAnimationFrame(animate);
   });
 